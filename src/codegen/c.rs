@@ -0,0 +1,665 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::codegen::{order_arguments, CodegenError};
+use crate::parser::{ASTNode, BinaryOp};
+use crate::suggest;
+
+const RUNTIME: &str = include_str!("runtime.c");
+
+// Tong functions the runtime provides, all taking (argc, argv)
+const BUILTINS: &[(&str, &str)] = &[
+    ("print", "tong_builtin_print"),
+    ("out", "tong_builtin_print"),
+    ("is_nan", "tong_builtin_is_nan"),
+    ("is_inf", "tong_builtin_is_inf"),
+];
+
+// valid Tong identifiers that C reserves, emitted with a trailing _
+const C_RESERVED: &[&str] = &[
+    "auto", "case", "char", "const", "continue", "default", "double",
+    "enum", "extern", "float", "for", "goto", "int", "long", "main", "register",
+    "short", "signed", "sizeof", "static", "struct", "switch", "typedef",
+    "union", "unsigned", "void", "volatile",
+];
+
+// Every Tong function becomes `static Value tong_fn_<name>(int argc, Value *argv)`
+// so defaults can be applied by the callee; nested functions are hoisted to
+// file scope as tong_nested_<n>_<name>, so two of the same name don't clash.
+// Variables first assigned outside any function become globals.
+pub fn emit(program: &[ASTNode]) -> Result<String, CodegenError> {
+    let mut generator = CGenerator {
+        out: String::new(),
+        functions_out: String::new(),
+        indent: 1,
+        functions: vec![HashMap::new()],
+        prototypes: Vec::new(),
+        nested: 0,
+        globals: Vec::new(),
+        scopes: Vec::new(),
+        enclosing: Vec::new(),
+        in_function: false,
+        temporaries: 0,
+        chained: 0,
+    };
+
+    for (name, arguments) in local_functions(program) {
+        generator.declare_function(name, format!("tong_fn_{}", name), arguments);
+    }
+    collect_globals(program, &mut generator.globals);
+
+    for node in program {
+        generator.emit_statement(node)?;
+    }
+
+    let mut code = String::from(RUNTIME);
+    code.push('\n');
+
+    generator.prototypes.sort();
+    for name in &generator.prototypes {
+        code.push_str(&format!("static Value {}(int argc, Value *argv);\n", name));
+    }
+    code.push('\n');
+
+    for name in &generator.globals {
+        code.push_str(&format!("static Value {};\n", ident(name)));
+    }
+    for i in 0..generator.temporaries {
+        code.push_str(&format!("static Value tong_tmp_{};\n", i));
+    }
+    code.push('\n');
+
+    code.push_str(&generator.functions_out);
+    code.push_str("int main(void) {\n");
+    code.push_str(&generator.out);
+    code.push_str("    return 0;\n}\n");
+
+    Ok(code)
+}
+
+// names assigned by top level code, outside of function bodies
+fn collect_globals(block: &[ASTNode], globals: &mut Vec<String>) {
+    for node in block {
+        match node {
+            ASTNode::VarDecNode { name, .. } | ASTNode::AssignNode { name, .. } if !globals.contains(name) => {
+                globals.push(name.clone());
+            }
+            ASTNode::IfElseNode { then_branch, elif_branch, else_branch, .. } => {
+                collect_globals(then_branch, globals);
+                for (_, elif_block) in elif_branch {
+                    collect_globals(elif_block, globals);
+                }
+                if let Some(else_block) = else_branch {
+                    collect_globals(else_block, globals);
+                }
+            }
+            ASTNode::LoopNode { block, .. } | ASTNode::DoWhileNode { block, .. } => collect_globals(block, globals),
+            _ => {}
+        }
+    }
+}
+
+// functions defined directly in a block, or in the if and loop blocks
+// inside it, but not inside other functions
+fn local_functions(block: &[ASTNode]) -> Vec<(&str, &[ASTNode])> {
+    let mut functions = Vec::new();
+    for node in block {
+        match node {
+            ASTNode::FuncDef { name, arguments, .. } => functions.push((name.as_str(), arguments.as_slice())),
+            ASTNode::IfElseNode { then_branch, elif_branch, else_branch, .. } => {
+                functions.extend(local_functions(then_branch));
+                for (_, elif_block) in elif_branch {
+                    functions.extend(local_functions(elif_block));
+                }
+                if let Some(else_block) = else_branch {
+                    functions.extend(local_functions(else_block));
+                }
+            }
+            ASTNode::LoopNode { block, .. } | ASTNode::DoWhileNode { block, .. } => functions.extend(local_functions(block)),
+            _ => {}
+        }
+    }
+    functions
+}
+
+struct CGenerator<'a> {
+    out: String,
+    functions_out: String,
+    indent: usize,
+    // the functions a call can reach, with their C names and parameters:
+    // the top level's, then those of each function being emitted
+    functions: Vec<HashMap<&'a str, (String, &'a [ASTNode])>>,
+    // C names of every function, declared before any is defined
+    prototypes: Vec<String>,
+    // number of tong_nested_<n>_ functions named so far
+    nested: usize,
+    globals: Vec<String>,
+    // locals of the function being emitted, one set per block
+    scopes: Vec<HashSet<String>>,
+    // locals of the functions around the one being emitted, which a
+    // function hoisted to file scope cannot reach
+    enclosing: Vec<HashSet<String>>,
+    in_function: bool,
+    // number of tong_tmp_<n> globals used by expressions
+    temporaries: usize,
+    // number of tong_chain_<n> locals used by chained comparisons
+    chained: usize,
+}
+
+impl<'a> CGenerator<'a> {
+
+    fn line(&mut self, text: &str) {
+        self.out.push_str(&"    ".repeat(self.indent));
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    // A temporary is only read right after it is set, before anything else
+    // can run, so one global per use site is safe even under recursion.
+    fn temporary(&mut self) -> String {
+        self.temporaries += 1;
+        format!("tong_tmp_{}", self.temporaries - 1)
+    }
+
+    fn declare_function(&mut self, name: &'a str, c_name: String, arguments: &'a [ASTNode]) {
+        self.prototypes.push(c_name.clone());
+        if let Some(functions) = self.functions.last_mut() {
+            functions.insert(name, (c_name, arguments));
+        }
+    }
+
+    // the innermost function of that name, nested ones shadow the top level's
+    fn function(&self, name: &str) -> Option<&(String, &'a [ASTNode])> {
+        self.functions.iter().rev().find_map(|functions| functions.get(name))
+    }
+
+    fn is_local(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.contains(name))
+    }
+
+    fn check_captured(&self, name: &str) -> Result<(), CodegenError> {
+        if !self.is_local(name) && self.enclosing.iter().any(|locals| locals.contains(name)) {
+            return Err(CodegenError::Unsupported("nested functions capturing locals".into(), "C".into()));
+        }
+        Ok(())
+    }
+
+    // C rejects a name nothing declares, so it is reported here instead
+    fn check_defined(&self, name: &str) -> Result<(), CodegenError> {
+        self.check_captured(name)?;
+        if self.is_local(name) || self.globals.iter().any(|global| global == name) {
+            return Ok(());
+        }
+        if self.function(name).is_some() || BUILTINS.iter().any(|(builtin, _)| *builtin == name) {
+            return Err(CodegenError::Unsupported("functions used as values".into(), "C".into()));
+        }
+        Err(self.undefined(name))
+    }
+
+    // the closest variable or function in scope is suggested for a typo
+    fn undefined(&self, name: &str) -> CodegenError {
+        let variables = self.scopes.iter().flatten().chain(&self.globals).map(String::as_str);
+        let functions = self.functions.iter().flat_map(|functions| functions.keys().copied());
+        let builtins = BUILTINS.iter().map(|(builtin, _)| *builtin);
+        let suggestion = suggest::closest(name, variables.chain(functions).chain(builtins));
+        CodegenError::Undefined(name.to_string(), suggestion.map(str::to_string))
+    }
+
+    fn emit_block(&mut self, block: &'a [ASTNode]) -> Result<(), CodegenError> {
+        self.scopes.push(HashSet::new());
+        self.indent += 1;
+        for node in block {
+            self.emit_statement(node)?;
+        }
+        self.indent -= 1;
+        self.scopes.pop();
+        Ok(())
+    }
+
+    fn emit_statement(&mut self, node: &'a ASTNode) -> Result<(), CodegenError> {
+        match node {
+            ASTNode::Eof => {}
+
+            ASTNode::ImportNode { path, alias, names } => {
+                if !path.ends_with(".h") {
+                    return Err(CodegenError::Unsupported("imports of Tong modules".into(), "C".into()));
+                }
+                if alias.is_some() || !names.is_empty() {
+                    return Err(CodegenError::Unsupported("aliased and selective header imports".into(), "C".into()));
+                }
+                self.functions_out.insert_str(0, &format!("#include <{}>\n", path));
+            }
+
+            // constants are checked by the parser, in C they are plain variables
+            ASTNode::VarDecNode { name, value, .. } => {
+                let value = self.expr(value)?;
+                if self.in_function {
+                    if let Some(scope) = self.scopes.last_mut() {
+                        scope.insert(name.clone());
+                    }
+                    self.line(&format!("Value {} = {};", ident(name), value));
+                } else {
+                    self.line(&format!("{} = {};", ident(name), value));
+                }
+            }
+
+            ASTNode::AssignNode { name, value } => {
+                self.check_captured(name)?;
+                let value = self.expr(value)?;
+                if self.in_function && !self.is_local(name) && !self.globals.contains(name) {
+                    if let Some(scope) = self.scopes.last_mut() {
+                        scope.insert(name.clone());
+                    }
+                    self.line(&format!("Value {} = {};", ident(name), value));
+                } else {
+                    self.line(&format!("{} = {};", ident(name), value));
+                }
+            }
+
+            ASTNode::IfElseNode { condition, then_branch, elif_branch, else_branch } => {
+                let condition = self.expr(condition)?;
+                self.line(&format!("if (tong_truthy({})) {{", condition));
+                self.emit_block(then_branch)?;
+
+                for (elif_condition, elif_block) in elif_branch {
+                    let elif_condition = self.expr(elif_condition)?;
+                    self.line(&format!("}} else if (tong_truthy({})) {{", elif_condition));
+                    self.emit_block(elif_block)?;
+                }
+
+                if let Some(else_block) = else_branch {
+                    self.line("} else {");
+                    self.emit_block(else_block)?;
+                }
+                self.line("}");
+            }
+
+            ASTNode::LoopNode { condition, block } => {
+                let condition = self.expr(condition)?;
+                self.line(&format!("while (tong_truthy({})) {{", condition));
+                self.emit_block(block)?;
+                self.line("}");
+            }
+
+            ASTNode::DoWhileNode { block, condition } => {
+                self.line("do {");
+                self.emit_block(block)?;
+                let condition = self.expr(condition)?;
+                self.line(&format!("}} while (tong_truthy({}));", condition));
+            }
+
+            ASTNode::BreakNode => self.line("break;"),
+
+            ASTNode::ReturnNode(value) => {
+                if !self.in_function {
+                    self.line("return 0;");
+                } else if let Some(value) = value {
+                    let value = self.expr(value)?;
+                    self.line(&format!("return {};", value));
+                } else {
+                    self.line("return tong_nil();");
+                }
+            }
+
+            // the C output is a single translation unit, pub changes nothing
+            ASTNode::FuncDef { name, arguments, block, .. } => self.emit_function(name, arguments, block)?,
+
+            ASTNode::DestructureNode { .. } => {
+                return Err(CodegenError::Unsupported("destructuring assignments".into(), "C".into()));
+            }
+
+            ASTNode::ClassDef { .. } => {
+                return Err(CodegenError::Unsupported("classes".into(), "C".into()));
+            }
+
+            ASTNode::FieldAssignNode { .. } => {
+                return Err(CodegenError::Unsupported("field assignments".into(), "C".into()));
+            }
+
+            ASTNode::FuncCall { .. } => {
+                let call = self.expr(node)?;
+                self.line(&format!("{};", call));
+            }
+
+            _ => {
+                let expr = self.expr(node)?;
+                self.line(&format!("(void){};", expr));
+            }
+        }
+
+        Ok(())
+    }
+
+    // functions are written to functions_out with their own scopes, then the
+    // state of whatever contained the definition is restored
+    fn emit_function(&mut self, name: &str, arguments: &'a [ASTNode], block: &'a [ASTNode]) -> Result<(), CodegenError> {
+        let c_name = self.function(name).map(|(c_name, _)| c_name.clone()).unwrap_or_default();
+        self.functions.push(HashMap::new());
+        for (nested, arguments) in local_functions(block) {
+            self.nested += 1;
+            let nested_name = format!("tong_nested_{}_{}", self.nested - 1, nested);
+            self.declare_function(nested, nested_name, arguments);
+        }
+
+        let out = std::mem::take(&mut self.out);
+        let scopes = std::mem::take(&mut self.scopes);
+        let indent = self.indent;
+        let in_function = self.in_function;
+        if in_function {
+            self.enclosing.push(scopes.iter().flatten().cloned().collect());
+        }
+
+        self.indent = 1;
+        self.in_function = true;
+
+        let mut params = HashSet::new();
+        for (i, arg) in arguments.iter().enumerate() {
+            if let ASTNode::ParamNode { name, default, variadic } = arg {
+                if *variadic {
+                    return Err(CodegenError::Unsupported("variadic parameters".into(), "C".into()));
+                }
+                let value = match default {
+                    Some(default) => {
+                        self.scopes.push(params.clone());
+                        let default = self.expr(default)?;
+                        self.scopes.pop();
+                        format!("argc > {0} && argv[{0}].type != TONG_NIL ? argv[{0}] : {1}", i, default)
+                    }
+                    None => format!("argc > {0} ? argv[{0}] : tong_nil()", i),
+                };
+                self.line(&format!("Value {} = {};", ident(name), value));
+                params.insert(name.clone());
+            }
+        }
+
+        self.scopes.push(params);
+        self.indent = 0;
+        self.emit_block(block)?;
+
+        let body = std::mem::replace(&mut self.out, out);
+        self.functions_out.push_str(&format!("static Value {}(int argc, Value *argv) {{\n", c_name));
+        self.functions_out.push_str(&body);
+        self.functions_out.push_str("    return tong_nil();\n}\n\n");
+
+        if in_function {
+            self.enclosing.pop();
+        }
+        self.functions.pop();
+        self.scopes = scopes;
+        self.indent = indent;
+        self.in_function = in_function;
+        Ok(())
+    }
+
+    fn expr(&mut self, node: &ASTNode) -> Result<String, CodegenError> {
+        let code = match node {
+            ASTNode::Integer(n) => format!("tong_int({}LL)", n),
+
+            ASTNode::Float(n) => {
+                if n.is_infinite() {
+                    "tong_float(INFINITY)".to_string()
+                } else {
+                    format!("tong_float({:?})", n)
+                }
+            }
+
+            ASTNode::StrLiteral(s) => format!("tong_str({})", string_literal(s)),
+
+            ASTNode::BoolNode(b) => format!("tong_bool({})", *b as i32),
+
+            ASTNode::Identifier(name) => {
+                self.check_defined(name)?;
+                ident(name)
+            }
+
+            ASTNode::UnaryOpNode { operand, .. } => format!("tong_neg({})", self.expr(operand)?),
+
+            ASTNode::BinOpNode { op, left, right, line } => {
+                let left = self.expr(left)?;
+                let right = self.expr(right)?;
+                let func = match op {
+                    BinaryOp::And => return Ok(format!("tong_bool(tong_truthy({}) && tong_truthy({}))", left, right)),
+                    BinaryOp::Or => return Ok(format!("tong_bool(tong_truthy({}) || tong_truthy({}))", left, right)),
+                    // left is evaluated once, right only when left is nil
+                    BinaryOp::Coalesce => {
+                        let tmp = self.temporary();
+                        return Ok(format!("({0} = {1}, {0}.type != TONG_NIL ? {0} : {2})", tmp, left, right));
+                    }
+                    BinaryOp::Add => "tong_add",
+                    BinaryOp::Sub => "tong_sub",
+                    BinaryOp::Mul => "tong_mul",
+                    BinaryOp::Div => "tong_div",
+                    BinaryOp::Mod => "tong_mod",
+                    BinaryOp::Pow => "tong_pow",
+                    BinaryOp::Eq => return Ok(format!("tong_eq({}, {})", left, right)),
+                    BinaryOp::Lt => "tong_lt",
+                    BinaryOp::Gt => "tong_gt",
+                    BinaryOp::Le => "tong_le",
+                    BinaryOp::Ge => "tong_ge",
+                    BinaryOp::In => "tong_in",
+                };
+                // the rest can fail at runtime and report the line
+                format!("{}({}, {}, {})", func, left, right, line)
+            }
+
+            // a call in the chain can recurse into this function before the
+            // operand it follows is compared, so operands get locals of a
+            // statement expression rather than temporaries
+            ASTNode::ChainedCompareNode { operands, ops } => {
+                let names: Vec<String> = (self.chained..self.chained + operands.len())
+                    .map(|i| format!("tong_chain_{}", i))
+                    .collect();
+                self.chained += operands.len();
+                let first = self.expr(&operands[0])?;
+                let second = self.expr(&operands[1])?;
+                let mut tests = Vec::new();
+                for (i, (op, line)) in ops.iter().enumerate() {
+                    let test = format!("tong_truthy({}({}, {}, {}))", comparison(*op), names[i], names[i + 1], line);
+                    if i == 0 {
+                        tests.push(test);
+                    } else {
+                        tests.push(format!("({} = {}, {})", names[i + 1], self.expr(&operands[i + 1])?, test));
+                    }
+                }
+                format!("({{ Value {} = {}, {} = {}, {}; tong_bool({}); }})",
+                    names[0], first, names[1], second, names[2..].join(", "), tests.join(" && "))
+            }
+
+            ASTNode::SpreadNode(_) => {
+                return Err(CodegenError::Unsupported("spread arguments".into(), "C".into()));
+            }
+
+            // the runtime's Value has no aggregate types yet
+            ASTNode::TupleNode(_) => {
+                return Err(CodegenError::Unsupported("tuples".into(), "C".into()));
+            }
+
+            ASTNode::MemberNode { .. } => {
+                return Err(CodegenError::Unsupported("field accesses".into(), "C".into()));
+            }
+
+            ASTNode::MethodCall { .. } => {
+                return Err(CodegenError::Unsupported("method calls".into(), "C".into()));
+            }
+
+            ASTNode::FuncCall { name, arguments } => self.call(name, arguments)?,
+
+            _ => unreachable!("{:?} is not an expression", node),
+        };
+
+        Ok(code)
+    }
+
+    fn call(&mut self, name: &str, arguments: &[ASTNode]) -> Result<String, CodegenError> {
+        let mut params = HashMap::new();
+        let func = match (self.function(name), BUILTINS.iter().find(|(builtin, _)| *builtin == name)) {
+            (Some((func, arguments)), _) => {
+                params.insert(name, *arguments);
+                func.clone()
+            }
+            (None, Some((_, func))) => func.to_string(),
+            (None, None) if self.is_local(name) || self.globals.iter().any(|global| global == name) => {
+                return Err(CodegenError::Unsupported("calls of variables".into(), "C".into()));
+            }
+            (None, None) => return Err(self.undefined(name)),
+        };
+
+        let mut args = Vec::new();
+        for slot in order_arguments(&params, name, arguments)? {
+            match slot {
+                Some(arg) => args.push(self.expr(arg)?),
+                None => args.push("tong_nil()".to_string()),
+            }
+        }
+
+        if args.is_empty() {
+            return Ok(format!("{}(0, NULL)", func));
+        }
+        Ok(format!("{}({}, (Value[]){{{}}})", func, args.len(), args.join(", ")))
+    }
+}
+
+fn comparison(op: BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Lt => "tong_lt",
+        BinaryOp::Gt => "tong_gt",
+        BinaryOp::Le => "tong_le",
+        BinaryOp::Ge => "tong_ge",
+        _ => unreachable!("{} does not chain", op),
+    }
+}
+
+fn ident(name: &str) -> String {
+    if C_RESERVED.contains(&name) || name.starts_with("tong_") {
+        format!("{}_", name)
+    } else {
+        name.to_string()
+    }
+}
+
+fn string_literal(s: &str) -> String {
+    let mut out = String::from("\"");
+    for byte in s.bytes() {
+        match byte {
+            b'"' => out.push_str("\\\""),
+            b'\\' => out.push_str("\\\\"),
+            b'\n' => out.push_str("\\n"),
+            b'\r' => out.push_str("\\r"),
+            b'\t' => out.push_str("\\t"),
+            0x20..=0x7e => out.push(byte as char),
+            _ => out.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::{Command, Output};
+
+    use super::*;
+    use crate::parser::tests::parse;
+
+    fn compile(source: &str) -> Result<String, CodegenError> {
+        emit(&parse(source).unwrap())
+    }
+
+    #[test]
+    fn undefined_names_are_errors() {
+        assert!(matches!(compile("print(y);"), Err(CodegenError::Undefined(name, None)) if name == "y"));
+        assert!(matches!(compile("pritn(1);"), Err(CodegenError::Undefined(name, _)) if name == "pritn"));
+        assert!(matches!(compile("let f = print;"), Err(CodegenError::Unsupported(..))));
+    }
+
+    #[test]
+    fn undefined_names_suggest_one_in_scope() {
+        let suggestion = |source| match compile(source) {
+            Err(CodegenError::Undefined(_, suggestion)) => suggestion,
+            other => panic!("expected an undefined name, got {:?}", other),
+        };
+        assert_eq!(suggestion("prnt(1);").as_deref(), Some("print"));
+        assert_eq!(suggestion("let count = 1;\nprint(cout);").as_deref(), Some("count"));
+        assert_eq!(suggestion("fn area(width) { return widh; }").as_deref(), Some("width"));
+        assert_eq!(suggestion("fn square(x) { return x; }\nprint(sqaure(2));").as_deref(), Some("square"));
+        assert_eq!(suggestion("let a = 1;\nprint(zebra);"), None);
+    }
+
+    #[test]
+    fn nested_functions_get_their_own_names() {
+        let code = compile("fn a() {\n    fn helper() { return 1; }\n    return helper();\n}\n\
+                            fn b() {\n    fn helper(x) { return x; }\n    return helper(x = 2);\n}\n\
+                            fn helper() { return 3; }\nprint(a(), b(), helper());\n").unwrap();
+        assert!(code.contains("static Value tong_nested_0_helper(int argc, Value *argv) {"));
+        assert!(code.contains("static Value tong_nested_1_helper(int argc, Value *argv) {"));
+        assert!(code.contains("return tong_nested_0_helper(0, NULL);"));
+        assert!(code.contains("return tong_nested_1_helper(1, (Value[]){tong_int(2LL)});"));
+        assert!(code.contains("tong_fn_helper(0, NULL)"));
+    }
+
+    // builds the program with cc and runs it, the tests using this need cc
+    // on the PATH: cargo test -- --ignored
+    fn run(source: &str, name: &str) -> Output {
+        let dir = std::env::temp_dir();
+        let c_file = dir.join(format!("tong_c_{}_{}.c", name, std::process::id()));
+        let binary = dir.join(format!("tong_c_{}_{}", name, std::process::id()));
+        std::fs::write(&c_file, compile(source).unwrap()).unwrap();
+        let built = Command::new("cc").arg(&c_file).arg("-o").arg(&binary).arg("-lm").status();
+        let output = built.ok().filter(|status| status.success()).map(|_| Command::new(&binary).output());
+        std::fs::remove_file(&c_file).unwrap();
+        let _ = std::fs::remove_file(&binary);
+        output.expect("cc should build the program").unwrap()
+    }
+
+    #[test]
+    #[ignore]
+    fn program_output_under_cc() {
+        let output = run("fn add(a, b = 2) { return a + b; }\nlet x = add(1);\nprint(x, add(b = 3, a = 1), 7 / 2, 2 ** 10, \"s\" + \"t\");\n", "output");
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "3 4 3 1024 st\n");
+    }
+
+
+    #[test]
+    fn coalescing_evaluates_its_left_side_once() {
+        let code = compile("fn f() { }\nlet x = f() ?? 1;\n").unwrap();
+        assert!(code.contains("static Value tong_tmp_0;"));
+        assert!(code.contains("x = (tong_tmp_0 = tong_fn_f(0, NULL), tong_tmp_0.type != TONG_NIL ? tong_tmp_0 : tong_int(1LL));"));
+        assert!(matches!(compile("let a = 1;\nlet b = a?.field;"), Err(CodegenError::Unsupported(..))));
+    }
+
+
+    #[test]
+    fn in_calls_the_runtime_with_its_line() {
+        let code = compile("let s = \"ab\";\n\nlet x = \"a\" in s;\n").unwrap();
+        assert!(code.contains("x = tong_in(tong_str(\"a\"), s, 3);"));
+    }
+
+
+    #[test]
+    fn do_while_is_a_c_do_while() {
+        let code = compile("let i = 0;\ndo {\n    i = i + 1;\n} while (i < 3);\n").unwrap();
+        assert!(code.contains("    do {\n        i = tong_add(i, tong_int(1LL), 3);\n    } while (tong_truthy(tong_lt(i, tong_int(3LL), 4)));\n"));
+    }
+
+
+    #[test]
+    fn classes_are_not_supported() {
+        assert!(matches!(compile("class A { }"), Err(CodegenError::Unsupported(..))));
+    }
+
+
+    #[test]
+    fn is_nan_and_is_inf_are_builtins() {
+        let code = compile("let x = 1.0 / 0.0;\nprint(is_inf(x), is_nan(x - x));\n").unwrap();
+        assert!(code.contains("tong_builtin_is_inf(1, (Value[]){x})"));
+        assert!(code.contains("tong_builtin_is_nan(1, (Value[]){tong_sub(x, x, 2)})"));
+    }
+
+    #[test]
+    #[ignore]
+    fn division_by_zero_under_cc() {
+        let output = run("let x = -7;\nprint(x / 2, x % 2, is_inf(x / 0.0), is_nan(0.0 / 0.0), is_nan(x));\nprint(x / 0);\n", "div");
+        assert!(!output.status.success());
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "-3 -1 true true false\n");
+        assert!(String::from_utf8_lossy(&output.stderr).contains("line 3: integer division by zero in"));
+    }
+}
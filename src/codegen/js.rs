@@ -0,0 +1,859 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::codegen::{collect_functions, order_arguments, CodegenError};
+use crate::parser::{ASTNode, BinaryOp, Pattern};
+
+// valid Tong identifiers that JavaScript reserves, emitted with a trailing _
+// like names starting with tong_, which are left to the helpers
+const JS_RESERVED: &[&str] = &[
+    "await", "case", "catch", "const", "continue", "debugger", "default",
+    "delete", "enum", "export", "extends", "finally", "for", "function",
+    "implements", "instanceof", "interface", "new", "null", "package",
+    "private", "protected", "public", "static", "super", "switch", "this",
+    "throw", "try", "typeof", "undefined", "var", "void", "with", "yield",
+];
+
+// Runtime checks for destructuring, JavaScript itself binds undefined to
+// whatever is missing. Only the helpers a program uses are emitted.
+const UNPACK_ELEMENTS: &str = "\
+function tong_unpack_elements(value, count) {
+    if (!Array.isArray(value) || value.length < count) {
+        throw new Error(\"not enough elements to unpack, expected \" + count);
+    }
+    return value;
+}
+";
+
+const UNPACK_KEYS: &str = "\
+function tong_unpack_keys(value, keys) {
+    for (const key of keys) {
+        if (value === null || typeof value !== \"object\" || !(key in value)) {
+            throw new Error(\"missing key '\" + key + \"' to unpack\");
+        }
+    }
+    return value;
+}
+";
+
+// JavaScript's own `in` looks at keys, Tong's looks at elements of arrays
+// and substrings of strings too
+const CONTAINS: &str = "\
+function tong_in(item, container) {
+    if (typeof container === \"string\" || Array.isArray(container)) {
+        return container.includes(item);
+    }
+    return container !== null && typeof container === \"object\" && item in container;
+}
+";
+
+// / and % between integers follow the C runtime: the result is truncated
+// and a zero divisor is an error. JavaScript cannot tell 7.0 from 7, so a
+// variable holding an integral float divides like an integer.
+const DIVIDE: &str = "\
+function tong_div(x, y, line) {
+    if (Number.isInteger(x) && Number.isInteger(y)) {
+        if (y === 0) {
+            throw new Error(\"line \" + line + \": integer division by zero in '\" + x + \" / \" + y + \"'\");
+        }
+        return Math.trunc(x / y);
+    }
+    return x / y;
+}
+";
+
+const MODULO: &str = "\
+function tong_mod(x, y, line) {
+    if (Number.isInteger(x) && Number.isInteger(y) && y === 0) {
+        throw new Error(\"line \" + line + \": integer division by zero in '\" + x + \" % \" + y + \"'\");
+    }
+    return x % y;
+}
+";
+
+// Tong functions written in JavaScript, emitted when a program calls one
+// without defining a function of the same name
+const BUILTINS: &[(&str, &str)] = &[
+    // print and out: arguments separated by spaces, then a newline. nil,
+    // nan and inf are spelled the way the C runtime spells them.
+    ("print", "\
+function print(...values) {
+    const text = (value) => value === undefined || value === null ? \"nil\"
+        : value === Infinity ? \"inf\" : value === -Infinity ? \"-inf\"
+        : Number.isNaN(value) ? \"nan\" : String(value);
+    console.log(values.map(text).join(\" \"));
+}
+"),
+    ("out", "\
+function out(...values) {
+    const text = (value) => value === undefined || value === null ? \"nil\"
+        : value === Infinity ? \"inf\" : value === -Infinity ? \"-inf\"
+        : Number.isNaN(value) ? \"nan\" : String(value);
+    console.log(values.map(text).join(\" \"));
+}
+"),
+    ("is_nan", "\
+function is_nan(value) {
+    return typeof value === \"number\" && Number.isNaN(value);
+}
+"),
+    ("is_inf", "\
+function is_inf(value) {
+    return typeof value === \"number\" && (value === Infinity || value === -Infinity);
+}
+"),
+];
+
+// Calling a class makes an instance: fields are set by the JavaScript
+// class itself, then init runs with the call's arguments.
+const NEW_INSTANCE: &str = "\
+function tong_new(cls, ...args) {
+    const object = new cls();
+    if (typeof object.init === \"function\") {
+        object.init(...args);
+    }
+    return object;
+}
+";
+
+// Methods a class defines to give its instances an operator. Only the
+// left operand is asked, and only operators some class overloads go
+// through the helper.
+const OVERLOADS: &[(BinaryOp, &str)] = &[
+    (BinaryOp::Add, "__add"),
+    (BinaryOp::Sub, "__sub"),
+    (BinaryOp::Mul, "__mul"),
+    (BinaryOp::Div, "__div"),
+    (BinaryOp::Mod, "__mod"),
+    (BinaryOp::Pow, "__pow"),
+    (BinaryOp::Eq, "__eq"),
+    (BinaryOp::Lt, "__lt"),
+    (BinaryOp::Gt, "__gt"),
+    (BinaryOp::Le, "__le"),
+    (BinaryOp::Ge, "__ge"),
+];
+
+const OVERLOAD: &str = "\
+function tong_overload(method, left, right, fallback) {
+    if (left !== null && typeof left === \"object\" && typeof left[method] === \"function\") {
+        return left[method](right);
+    }
+    return fallback(left, right);
+}
+";
+
+pub fn emit(program: &[ASTNode]) -> Result<String, CodegenError> {
+    let mut generator = JsGenerator {
+        out: String::new(),
+        indent: 0,
+        functions: HashMap::new(),
+        classes: HashMap::new(),
+        overloads: HashSet::new(),
+        scopes: vec![HashSet::new()],
+        helpers: Vec::new(),
+        in_method: false,
+    };
+
+    collect_functions(program, &mut generator.functions);
+    collect_classes(program, &mut generator.classes, &mut generator.overloads);
+
+    // a class without its own init is constructed by the nearest ancestor's
+    let classes: Vec<&str> = generator.classes.keys().copied().collect();
+    for class in classes {
+        let mut current = class;
+        for _ in 0..generator.classes.len() {
+            if let Some(params) = generator.functions.get(current).copied() {
+                generator.functions.insert(class, params);
+                break;
+            }
+            match generator.classes.get(current) {
+                Some(Some(parent)) => current = parent,
+                _ => break,
+            }
+        }
+    }
+    for node in program {
+        generator.emit_statement(node)?;
+    }
+
+    let mut code = String::new();
+    for helper in &generator.helpers {
+        code.push_str(helper);
+        code.push('\n');
+    }
+    code.push_str(&generator.out);
+
+    Ok(code)
+}
+
+// every class in the program and the class it inherits from, and the
+// operators their methods overload
+fn collect_classes<'a>(
+    block: &'a [ASTNode],
+    classes: &mut HashMap<&'a str, Option<&'a str>>,
+    overloads: &mut HashSet<BinaryOp>,
+) {
+    for node in block {
+        match node {
+            ASTNode::ClassDef { name, parent, methods, .. } => {
+                classes.insert(name, parent.as_deref());
+                for method in methods {
+                    if let ASTNode::FuncDef { name, .. } = method {
+                        if let Some((op, _)) = OVERLOADS.iter().find(|(_, method)| method == name) {
+                            overloads.insert(*op);
+                        }
+                    }
+                }
+                collect_classes(methods, classes, overloads);
+            }
+            ASTNode::FuncDef { block, .. }
+            | ASTNode::LoopNode { block, .. }
+            | ASTNode::DoWhileNode { block, .. } => collect_classes(block, classes, overloads),
+            ASTNode::IfElseNode { then_branch, elif_branch, else_branch, .. } => {
+                collect_classes(then_branch, classes, overloads);
+                for (_, elif_block) in elif_branch {
+                    collect_classes(elif_block, classes, overloads);
+                }
+                if let Some(else_block) = else_branch {
+                    collect_classes(else_block, classes, overloads);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+struct JsGenerator<'a> {
+    out: String,
+    indent: usize,
+    // parameter lists of every function in the program, for named arguments
+    functions: HashMap<&'a str, &'a [ASTNode]>,
+    // every class in the program with its parent, calling one creates an
+    // instance
+    classes: HashMap<&'a str, Option<&'a str>>,
+    // operators some class defines a method for
+    overloads: HashSet<BinaryOp>,
+    // names bound in each enclosing block; Tong creates a variable on its
+    // first assignment, JavaScript needs a `let` for it
+    scopes: Vec<HashSet<String>>,
+    helpers: Vec<&'static str>,
+    // inside a method body self is the instance, `this` in JavaScript
+    in_method: bool,
+}
+
+impl<'a> JsGenerator<'a> {
+
+    fn line(&mut self, text: &str) {
+        self.out.push_str(&"    ".repeat(self.indent));
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn declare(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string());
+        }
+    }
+
+    fn is_declared(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.contains(name))
+    }
+
+    fn helper(&mut self, helper: &'static str) {
+        if !self.helpers.contains(&helper) {
+            self.helpers.push(helper);
+        }
+    }
+
+    fn emit_block(&mut self, block: &'a [ASTNode], params: &[String]) -> Result<(), CodegenError> {
+        self.scopes.push(params.iter().cloned().collect());
+        self.indent += 1;
+        for node in block {
+            self.emit_statement(node)?;
+        }
+        self.indent -= 1;
+        self.scopes.pop();
+        Ok(())
+    }
+
+    fn emit_statement(&mut self, node: &'a ASTNode) -> Result<(), CodegenError> {
+        match node {
+            ASTNode::Eof => {}
+
+            ASTNode::ImportNode { path, alias, names } => {
+                let path = match path.strip_suffix(".tong") {
+                    Some(stem) => format!("{}.js", stem),
+                    None => path.clone(),
+                };
+                if let Some(alias) = alias {
+                    self.declare(alias);
+                    self.line(&format!("import * as {} from {};", ident(alias), string_literal(&path)));
+                } else if !names.is_empty() {
+                    for name in names {
+                        self.declare(name);
+                    }
+                    let names: Vec<String> = names.iter().map(|name| ident(name)).collect();
+                    self.line(&format!("import {{ {} }} from {};", names.join(", "), string_literal(&path)));
+                } else {
+                    self.line(&format!("import {};", string_literal(&path)));
+                }
+            }
+
+            ASTNode::VarDecNode { name, value, constant, public } => {
+                let value = self.bare_expr(value)?;
+                self.declare(name);
+                let keyword = if *constant { "const" } else { "let" };
+                self.line(&format!("{}{} {} = {};", export(*public), keyword, ident(name), value));
+            }
+
+            ASTNode::AssignNode { name, value } => {
+                let value = self.bare_expr(value)?;
+                if self.is_declared(name) {
+                    self.line(&format!("{} = {};", ident(name), value));
+                } else {
+                    self.declare(name);
+                    self.line(&format!("let {} = {};", ident(name), value));
+                }
+            }
+
+            // tuples are arrays in JavaScript and maps are objects, both
+            // unpacked with JavaScript's own destructuring
+            ASTNode::DestructureNode { pattern, value, constant, declare } => {
+                let value = self.bare_expr(value)?;
+                let names = pattern.names();
+                let (target, value) = match pattern {
+                    Pattern::Tuple(_) | Pattern::Array(_) => {
+                        self.helper(UNPACK_ELEMENTS);
+                        let targets: Vec<String> = names.iter().map(|name| ident(name)).collect();
+                        (format!("[{}]", targets.join(", ")), format!("tong_unpack_elements({}, {})", value, names.len()))
+                    }
+                    Pattern::Map(_) => {
+                        self.helper(UNPACK_KEYS);
+                        let targets: Vec<String> = names.iter()
+                            .map(|name| if ident(name) == *name { ident(name) } else { format!("{}: {}", name, ident(name)) })
+                            .collect();
+                        let keys: Vec<String> = names.iter().map(|name| string_literal(name)).collect();
+                        (format!("{{ {} }}", targets.join(", ")), format!("tong_unpack_keys({}, [{}])", value, keys.join(", ")))
+                    }
+                };
+
+                let undeclared: Vec<&String> = names.iter().filter(|name| !self.is_declared(name)).collect();
+                if *declare || undeclared.len() == names.len() {
+                    let keyword = if *constant { "const" } else { "let" };
+                    self.line(&format!("{} {} = {};", keyword, target, value));
+                } else {
+                    for name in undeclared {
+                        self.line(&format!("let {};", ident(name)));
+                    }
+                    self.line(&format!("{} = {};", target, value));
+                }
+                for name in names {
+                    self.declare(name);
+                }
+            }
+
+            ASTNode::IfElseNode { condition, then_branch, elif_branch, else_branch } => {
+                let condition = self.bare_expr(condition)?;
+                self.line(&format!("if ({}) {{", condition));
+                self.emit_block(then_branch, &[])?;
+
+                for (elif_condition, elif_block) in elif_branch {
+                    let elif_condition = self.bare_expr(elif_condition)?;
+                    self.line(&format!("}} else if ({}) {{", elif_condition));
+                    self.emit_block(elif_block, &[])?;
+                }
+
+                if let Some(else_block) = else_branch {
+                    self.line("} else {");
+                    self.emit_block(else_block, &[])?;
+                }
+                self.line("}");
+            }
+
+            ASTNode::LoopNode { condition, block } => {
+                let condition = self.bare_expr(condition)?;
+                self.line(&format!("while ({}) {{", condition));
+                self.emit_block(block, &[])?;
+                self.line("}");
+            }
+
+            ASTNode::DoWhileNode { block, condition } => {
+                self.line("do {");
+                self.emit_block(block, &[])?;
+                let condition = self.bare_expr(condition)?;
+                self.line(&format!("}} while ({});", condition));
+            }
+
+            ASTNode::BreakNode => self.line("break;"),
+
+            ASTNode::ReturnNode(value) => {
+                match value {
+                    Some(value) => {
+                        let value = self.bare_expr(value)?;
+                        self.line(&format!("return {};", value));
+                    }
+                    None => self.line("return;"),
+                }
+            }
+
+            ASTNode::FuncDef { name, arguments, block, public } => {
+                self.declare(name);
+
+                let (names, params) = self.params(arguments)?;
+                self.line(&format!("{}function {}({}) {{", export(*public), ident(name), params.join(", ")));
+                let in_method = std::mem::replace(&mut self.in_method, false);
+                self.emit_block(block, &names)?;
+                self.in_method = in_method;
+                self.line("}");
+            }
+
+            ASTNode::ClassDef { name, parent, fields, methods } => {
+                self.declare(name);
+                match parent {
+                    Some(parent) => self.line(&format!("class {} extends {} {{", ident(name), ident(parent))),
+                    None => self.line(&format!("class {} {{", ident(name))),
+                }
+                self.indent += 1;
+
+                let in_method = std::mem::replace(&mut self.in_method, true);
+                for field in fields {
+                    if let ASTNode::VarDecNode { name, value, .. } = field {
+                        let value = self.bare_expr(value)?;
+                        self.line(&format!("{} = {};", name, value));
+                    }
+                }
+                for method in methods {
+                    if let ASTNode::FuncDef { name, arguments, block, .. } = method {
+                        let (names, params) = self.params(arguments)?;
+                        self.line(&format!("{}({}) {{", name, params.join(", ")));
+                        self.emit_block(block, &names)?;
+                        self.line("}");
+                        // __str is how an instance turns into a string
+                        if name == "__str" {
+                            self.line("toString() {");
+                            self.line("    return this.__str();");
+                            self.line("}");
+                        }
+                    }
+                }
+                self.in_method = in_method;
+
+                self.indent -= 1;
+                self.line("}");
+            }
+
+            ASTNode::FieldAssignNode { object, field, value } => {
+                let object = self.expr(object)?;
+                let value = self.bare_expr(value)?;
+                self.line(&format!("{}.{} = {};", object, field, value));
+            }
+
+            _ => {
+                let expr = self.bare_expr(node)?;
+                self.line(&format!("{};", expr));
+            }
+        }
+
+        Ok(())
+    }
+
+    // the names a function binds and its JavaScript parameter list
+    fn params(&mut self, arguments: &[ASTNode]) -> Result<(Vec<String>, Vec<String>), CodegenError> {
+        let mut names = Vec::new();
+        let mut params = Vec::new();
+        for arg in arguments {
+            if let ASTNode::ParamNode { name, default, variadic } = arg {
+                names.push(name.clone());
+                params.push(match default {
+                    _ if *variadic => format!("...{}", ident(name)),
+                    Some(default) => format!("{} = {}", ident(name), self.bare_expr(default)?),
+                    None => ident(name),
+                });
+            }
+        }
+        Ok((names, params))
+    }
+
+    // binary operations are always parenthesized inside other expressions so
+    // Tong's precedence carries over; where the expression stands alone
+    // (statements, conditions, arguments) the outer parentheses are dropped
+    fn bare_expr(&mut self, node: &ASTNode) -> Result<String, CodegenError> {
+        match node {
+            ASTNode::BinOpNode { op, left, right, line } => {
+                if *op == BinaryOp::In {
+                    self.helper(CONTAINS);
+                    return Ok(format!("tong_in({}, {})", self.bare_expr(left)?, self.bare_expr(right)?));
+                }
+                // float literals like 7.0 come out as 7, so division with
+                // one on either side is left to JavaScript
+                let divide = match op {
+                    BinaryOp::Div | BinaryOp::Mod if !is_float(left) && !is_float(right) => {
+                        let (name, helper) = if *op == BinaryOp::Div { ("tong_div", DIVIDE) } else { ("tong_mod", MODULO) };
+                        self.helper(helper);
+                        Some(name)
+                    }
+                    _ => None,
+                };
+                let symbol = match op {
+                    BinaryOp::And => "&&",
+                    BinaryOp::Or => "||",
+                    BinaryOp::Eq => "===",
+                    op => op.symbol(),
+                };
+                if self.overloads.contains(op) {
+                    let method = OVERLOADS.iter().find(|(overloaded, _)| overloaded == op).map_or("", |(_, method)| method);
+                    self.helper(OVERLOAD);
+                    let fallback = match divide {
+                        Some(name) => format!("(x, y) => {}(x, y, {})", name, line),
+                        None => format!("(x, y) => x {} y", symbol),
+                    };
+                    return Ok(format!("tong_overload(\"{}\", {}, {}, {})",
+                        method, self.bare_expr(left)?, self.bare_expr(right)?, fallback));
+                }
+                if let Some(name) = divide {
+                    return Ok(format!("{}({}, {}, {})", name, self.bare_expr(left)?, self.bare_expr(right)?, line));
+                }
+                Ok(format!("{} {} {}", self.expr(left)?, symbol, self.expr(right)?))
+            }
+            _ => self.expr(node),
+        }
+    }
+
+    fn comparison(&mut self, op: BinaryOp, left: &str, right: &str) -> String {
+        if !self.overloads.contains(&op) {
+            return format!("{} {} {}", left, op, right);
+        }
+        let method = OVERLOADS.iter().find(|(overloaded, _)| *overloaded == op).map_or("", |(_, method)| method);
+        self.helper(OVERLOAD);
+        format!("tong_overload(\"{}\", {}, {}, (x, y) => x {} y)", method, left, right, op)
+    }
+
+    fn expr(&mut self, node: &ASTNode) -> Result<String, CodegenError> {
+        let code = match node {
+            ASTNode::Integer(n) => n.to_string(),
+
+            ASTNode::Float(n) => {
+                if n.is_infinite() {
+                    "Infinity".to_string()
+                } else {
+                    n.to_string()
+                }
+            }
+
+            ASTNode::StrLiteral(s) => string_literal(s),
+
+            ASTNode::BoolNode(b) => b.to_string(),
+
+            ASTNode::Identifier(name) if self.in_method && name == "self" => "this".to_string(),
+
+            ASTNode::Identifier(name) if self.in_method && name == "super" => "super".to_string(),
+
+            ASTNode::Identifier(name) => ident(name),
+
+            ASTNode::UnaryOpNode { op, operand } => format!("({}{})", op, self.expr(operand)?),
+
+            ASTNode::BinOpNode { .. } => format!("({})", self.bare_expr(node)?),
+
+            // an arrow function gives each operand a local and keeps this
+            ASTNode::ChainedCompareNode { operands, ops } => {
+                let names: Vec<String> = (0..operands.len()).map(|i| format!("tong_chain_{}", i)).collect();
+                let first = self.bare_expr(&operands[0])?;
+                let second = self.bare_expr(&operands[1])?;
+                let mut tests = Vec::new();
+                for (i, (op, _)) in ops.iter().enumerate() {
+                    let test = self.comparison(*op, &names[i], &names[i + 1]);
+                    if i == 0 {
+                        tests.push(test);
+                    } else {
+                        tests.push(format!("({} = {}, {})", names[i + 1], self.bare_expr(&operands[i + 1])?, test));
+                    }
+                }
+                format!("(() => {{ let {} = {}, {} = {}, {}; return {}; }})()",
+                    names[0], first, names[1], second, names[2..].join(", "), tests.join(" && "))
+            }
+
+            ASTNode::SpreadNode(value) => format!("...{}", self.expr(value)?),
+
+            ASTNode::MemberNode { object, field, optional } => {
+                let object = match **object {
+                    // 1.x would lex as a number
+                    ASTNode::Integer(_) | ASTNode::Float(_) => format!("({})", self.expr(object)?),
+                    _ => self.expr(object)?,
+                };
+                format!("{}{}{}", object, if *optional { "?." } else { "." }, field)
+            }
+
+            ASTNode::TupleNode(items) => {
+                let mut values = Vec::new();
+                for item in items {
+                    values.push(self.bare_expr(item)?);
+                }
+                format!("[{}]", values.join(", "))
+            }
+
+            ASTNode::FuncCall { name, arguments } => self.call(name, arguments)?,
+
+            ASTNode::MethodCall { object, method, arguments, optional } => {
+                let object = self.expr(object)?;
+                let mut args = Vec::new();
+                for arg in arguments {
+                    match arg {
+                        ASTNode::NamedArgNode { .. } => {
+                            return Err(CodegenError::Unsupported("named arguments to methods".into(), "JS".into()));
+                        }
+                        _ => args.push(self.bare_expr(arg)?),
+                    }
+                }
+                format!("{}{}{}({})", object, if *optional { "?." } else { "." }, method, args.join(", "))
+            }
+
+            _ => unreachable!("{:?} is not an expression", node),
+        };
+
+        Ok(code)
+    }
+
+    fn call(&mut self, name: &str, arguments: &[ASTNode]) -> Result<String, CodegenError> {
+        let mut args = Vec::new();
+        for slot in order_arguments(&self.functions, name, arguments)? {
+            match slot {
+                Some(arg) => args.push(self.bare_expr(arg)?),
+                None => args.push("undefined".to_string()),
+            }
+        }
+
+        if let Some((_, builtin)) = BUILTINS.iter().find(|(builtin, _)| *builtin == name) {
+            if !self.functions.contains_key(name) && !self.is_declared(name) {
+                self.helper(builtin);
+            }
+        }
+
+        if self.classes.contains_key(name) {
+            self.helper(NEW_INSTANCE);
+            args.insert(0, ident(name));
+            return Ok(format!("tong_new({})", args.join(", ")));
+        }
+        Ok(format!("{}({})", ident(name), args.join(", ")))
+    }
+}
+
+// pub definitions become ES module exports
+fn export(public: bool) -> &'static str {
+    if public { "export " } else { "" }
+}
+
+fn ident(name: &str) -> String {
+    if JS_RESERVED.contains(&name) || name.starts_with("tong_") {
+        format!("{}_", name)
+    } else {
+        name.to_string()
+    }
+}
+
+fn string_literal(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// whether an operand is a float whatever the variables in it hold
+fn is_float(node: &ASTNode) -> bool {
+    match node {
+        ASTNode::Float(_) => true,
+        ASTNode::UnaryOpNode { operand, .. } => is_float(operand),
+        ASTNode::BinOpNode { op, left, right, .. } => matches!(
+            op,
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod | BinaryOp::Pow
+        ) && (is_float(left) || is_float(right)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::*;
+    use crate::parser::tests::parse;
+
+    fn compile(source: &str) -> String {
+        emit(&parse(source).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn print_is_emitted_as_a_helper() {
+        let js = compile("print(\"a\", 1);\nout(\"b\");\n");
+        assert!(js.starts_with("function print(...values) {\n"));
+        assert!(js.contains("console.log(values.map(text).join(\" \"));"));
+        assert!(js.contains("function out(...values) {\n"));
+        assert!(js.ends_with("print(\"a\", 1);\nout(\"b\");\n"));
+    }
+
+    #[test]
+    fn defining_print_replaces_the_helper() {
+        let js = compile("fn print(x) { return x; }\nprint(1);\n");
+        assert!(!js.contains("console.log"));
+    }
+
+    #[test]
+    fn tong_names_cannot_clash_with_helpers() {
+        let js = compile("let tong_in = 1;\n");
+        assert!(js.contains("let tong_in_ = 1;"));
+    }
+
+    #[test]
+    fn integer_division_goes_through_a_helper() {
+        let js = compile("let x = 7;\nlet y = x / 2;\nlet z = x % 0;\n");
+        assert!(js.contains("function tong_div(x, y, line) {"));
+        assert!(js.contains("return Math.trunc(x / y);"));
+        assert!(js.contains("function tong_mod(x, y, line) {"));
+        assert!(js.contains("let y = tong_div(x, 2, 2);"));
+        assert!(js.contains("let z = tong_mod(x, 0, 3);"));
+    }
+
+    #[test]
+    fn float_division_is_left_to_javascript() {
+        let js = compile("let x = 7;\nlet y = x / 2.0;\nlet z = -1.5 * x % 2;\n");
+        assert!(!js.contains("tong_div"));
+        assert!(!js.contains("tong_mod"));
+        assert!(js.contains("let y = x / 2;"));
+    }
+
+    #[test]
+    fn chained_comparison_calls_its_middle_once() {
+        let js = compile("fn f() { return 1; }\nlet x = 0 < f() < 10;\n");
+        assert_eq!(js.matches("= f()").count(), 1);
+        assert!(js.contains("let tong_chain_0 = 0, tong_chain_1 = f(), tong_chain_2;"));
+        assert!(js.contains("(tong_chain_2 = 10, tong_chain_1 < tong_chain_2)"));
+    }
+
+    // needs node on the PATH: cargo test -- --ignored
+    #[test]
+    #[ignore]
+    fn print_output_under_node() {
+        let path = std::env::temp_dir().join(format!("tong_js_test_{}.js", std::process::id()));
+        std::fs::write(&path, compile("let x = 1.5;\nprint(\"a\", 1, x, true);\nout(\"b\");\n")).unwrap();
+        let output = Command::new("node").arg(&path).output();
+        std::fs::remove_file(&path).unwrap();
+        let output = output.expect("node should be installed");
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "a 1 1.5 true\nb\n");
+    }
+
+    #[test]
+    #[ignore]
+    fn integer_division_under_node() {
+        let path = std::env::temp_dir().join(format!("tong_js_div_test_{}.js", std::process::id()));
+        std::fs::write(&path, compile("let x = -7;\nprint(x / 2, x % 2, x / 2.0);\nprint(x / 0);\n")).unwrap();
+        let output = Command::new("node").arg(&path).output();
+        std::fs::remove_file(&path).unwrap();
+        let output = output.expect("node should be installed");
+        assert!(!output.status.success());
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "-3 -1 -3.5\n");
+        assert!(String::from_utf8_lossy(&output.stderr).contains("line 3: integer division by zero in '-7 / 0'"));
+    }
+
+
+    #[test]
+    fn only_pub_declarations_are_exported() {
+        let js = compile("pub fn f() { return 1; }\nfn g() { return 2; }\npub let x = 1;\nconst y = 2;\n");
+        assert!(js.contains("export function f()"));
+        assert!(js.contains("\nfunction g()"));
+        assert!(js.contains("export let x = 1;"));
+        assert!(js.contains("\nconst y = 2;"));
+    }
+
+
+    #[test]
+    fn imports_bind_their_names() {
+        let js = compile("import \"utils/strings.tong\" as s;\nimport \"math.tong\" { sqrt, pow }\nimport \"./side.js\";\n");
+        assert!(js.contains("import * as s from \"utils/strings.js\";"));
+        assert!(js.contains("import { sqrt, pow } from \"math.js\";"));
+        assert!(js.contains("import \"./side.js\";"));
+    }
+
+
+    #[test]
+    fn tuples_are_arrays_unpacked_by_length() {
+        let js = compile("let (a, b) = (1, 2);\n(a, b) = (b, a);\n");
+        assert!(js.contains("let [a, b] = tong_unpack_elements([1, 2], 2);"));
+        assert!(js.contains("[a, b] = tong_unpack_elements([b, a], 2);"));
+    }
+
+
+    #[test]
+    fn map_destructuring_checks_keys_and_renames_reserved_names() {
+        let js = compile("let person = 1;\nlet [first] = person;\nlet { name, this } = person;\n");
+        assert!(js.contains("let [first] = tong_unpack_elements(person, 1);"));
+        assert!(js.contains("let { name, this: this_ } = tong_unpack_keys(person, [\"name\", \"this\"]);"));
+    }
+
+
+    #[test]
+    fn optional_chaining_and_coalescing_are_javascript_operators() {
+        let js = compile("let a = 1;\nlet y = a?.b.c;\nlet z = a?.f(1) ?? 2 > 1;\n");
+        assert!(js.contains("let y = a?.b.c;"));
+        assert!(js.contains("let z = (a?.f(1) ?? 2) > 1;"));
+    }
+
+
+    #[test]
+    fn in_goes_through_a_helper() {
+        let js = compile("let s = \"ab\";\nlet x = \"a\" in s;\n");
+        assert!(js.contains("function tong_in(item, container) {"));
+        assert!(js.contains("let x = tong_in(\"a\", s);"));
+    }
+
+
+    #[test]
+    fn do_while_is_a_javascript_do_while() {
+        let js = compile("let i = 0;\ndo {\n    i = i + 1;\n} while (i < 3);\n");
+        assert!(js.contains("do {\n    i = i + 1;\n} while (i < 3);\n"));
+    }
+
+
+    #[test]
+    fn loop_without_a_condition_is_while_true() {
+        let js = compile("loop {\n    break;\n}\n");
+        assert!(js.contains("while (true) {\n    break;\n}\n"));
+    }
+
+
+    #[test]
+    fn classes_are_javascript_classes() {
+        let js = compile("class Point {\n    let x = 0;\n    fn init(x) {\n        self.x = x;\n    }\n}\n\
+                          class Point3 : Point {\n}\nlet p = Point3(2);\n");
+        assert!(js.contains("class Point {\n    x = 0;\n    init(x) {\n        this.x = x;\n    }\n}\n"));
+        assert!(js.contains("class Point3 extends Point {"));
+        assert!(js.contains("let p = tong_new(Point3, 2);"));
+    }
+
+
+    #[test]
+    fn only_overloaded_operators_ask_the_left_operand() {
+        let js = compile("class V {\n    fn __add(other) { return self; }\n    fn __str() { return \"V\"; }\n}\n\
+                          let a = V();\nlet b = a + a - 1;\n");
+        assert!(js.contains("function tong_overload(method, left, right, fallback) {"));
+        assert!(js.contains("let b = (tong_overload(\"__add\", a, a, (x, y) => x + y)) - 1;"));
+        assert!(js.contains("    toString() {\n        return this.__str();\n    }\n"));
+        assert!(!compile("let a = 1;\nlet b = a + a;\n").contains("tong_overload"));
+    }
+
+
+    #[test]
+    fn is_nan_and_is_inf_are_emitted_when_used() {
+        let js = compile("let x = 1.0 / 0.0;\nprint(is_inf(x));\n");
+        assert!(js.contains("function is_inf(value) {"));
+        assert!(!js.contains("function is_nan(value) {"));
+    }
+}
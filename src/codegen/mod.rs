@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::parser::ASTNode;
+
+pub mod c;
+pub mod js;
+
+#[derive(Debug)]
+pub enum CodegenError {
+    UnknownNamedArgument(String, String),
+    NamedArgumentsToUnknownFunction(String),
+    ArgumentGivenTwice(String, String),
+    Unsupported(String, String),
+    // the name, and the closest one in scope if it looks like a typo
+    Undefined(String, Option<String>),
+}
+
+impl fmt::Display for CodegenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CodegenError::UnknownNamedArgument(func, arg) => {
+                write!(f, "'{}' has no parameter named '{}'", func, arg)
+            }
+            CodegenError::NamedArgumentsToUnknownFunction(func) => {
+                write!(f, "named arguments passed to '{}', which is not defined in this program", func)
+            }
+            CodegenError::ArgumentGivenTwice(func, arg) => {
+                write!(f, "argument '{}' of '{}' is given both by position and by name", arg, func)
+            }
+            CodegenError::Unsupported(what, target) => {
+                write!(f, "{} are not supported by the {} backend", what, target)
+            }
+            CodegenError::Undefined(name, _) => write!(f, "'{}' is not defined", name),
+        }
+    }
+}
+
+// parameter lists of every function defined anywhere in the program
+fn collect_functions<'a>(block: &'a [ASTNode], functions: &mut HashMap<&'a str, &'a [ASTNode]>) {
+    for node in block {
+        match node {
+            ASTNode::FuncDef { name, arguments, block, .. } => {
+                functions.insert(name, arguments);
+                collect_functions(block, functions);
+            }
+            ASTNode::IfElseNode { then_branch, elif_branch, else_branch, .. } => {
+                collect_functions(then_branch, functions);
+                for (_, elif_block) in elif_branch {
+                    collect_functions(elif_block, functions);
+                }
+                if let Some(else_block) = else_branch {
+                    collect_functions(else_block, functions);
+                }
+            }
+            ASTNode::LoopNode { block, .. } | ASTNode::DoWhileNode { block, .. } => collect_functions(block, functions),
+            // calling a class runs its init, so named arguments go by init's
+            // parameters; methods themselves are not free functions
+            ASTNode::ClassDef { name, methods, .. } => {
+                for method in methods {
+                    if let ASTNode::FuncDef { name: method_name, arguments, block, .. } = method {
+                        if method_name == "init" {
+                            functions.insert(name, arguments);
+                        }
+                        collect_functions(block, functions);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Neither target has named arguments, so they are moved into the slot of
+// the matching parameter. Slots nobody filled are None and the backends
+// pass their "missing" value there, which also makes the callee apply the
+// parameter's default.
+fn order_arguments<'n>(
+    functions: &HashMap<&str, &[ASTNode]>,
+    name: &str,
+    arguments: &'n [ASTNode],
+) -> Result<Vec<Option<&'n ASTNode>>, CodegenError> {
+    let mut slots: Vec<Option<&ASTNode>> = Vec::new();
+    let mut named = Vec::new();
+
+    for arg in arguments {
+        match arg {
+            ASTNode::NamedArgNode { name, value } => named.push((name, value)),
+            _ => slots.push(Some(arg)),
+        }
+    }
+
+    if named.is_empty() {
+        return Ok(slots);
+    }
+
+    let params = match functions.get(name) {
+        Some(params) => *params,
+        None => return Err(CodegenError::NamedArgumentsToUnknownFunction(name.to_string())),
+    };
+
+    for (arg_name, value) in named {
+        let index = params.iter()
+            .position(|param| matches!(param,
+                ASTNode::ParamNode { name, variadic: false, .. } if name == arg_name));
+        let index = match index {
+            Some(index) => index,
+            None => return Err(CodegenError::UnknownNamedArgument(name.to_string(), arg_name.clone())),
+        };
+
+        if index < slots.len() && slots[index].is_some() {
+            return Err(CodegenError::ArgumentGivenTwice(name.to_string(), arg_name.clone()));
+        }
+        if index >= slots.len() {
+            slots.resize(index + 1, None);
+        }
+        slots[index] = Some(value);
+    }
+
+    Ok(slots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::tests::parse;
+
+    // the slots of the last call in source, by the function definitions in it
+    fn slots(source: &str) -> Result<Vec<Option<ASTNode>>, CodegenError> {
+        let program = parse(source).unwrap();
+        let mut functions = HashMap::new();
+        collect_functions(&program, &mut functions);
+        match program.last() {
+            Some(ASTNode::FuncCall { name, arguments }) => {
+                let slots = order_arguments(&functions, name, arguments)?;
+                Ok(slots.into_iter().map(|slot| slot.cloned()).collect())
+            }
+            other => panic!("expected a call, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn named_arguments_fill_their_parameter_slot() {
+        let defined = "fn f(a, b = 2, c = 3) { return a; }\n";
+        let (one, two) = (Some(ASTNode::Integer(1)), Some(ASTNode::Integer(2)));
+        assert_eq!(slots(&format!("{}f(1, c = 2);", defined)).unwrap(), vec![one.clone(), None, two.clone()]);
+        assert_eq!(slots(&format!("{}f(b = 2, a = 1);", defined)).unwrap(), vec![one.clone(), two]);
+        assert_eq!(slots("g(1);").unwrap(), vec![one]);
+    }
+
+    #[test]
+    fn named_arguments_must_match_one_parameter() {
+        let defined = "fn f(a, b = 2) { return a; }\n";
+        assert!(matches!(slots(&format!("{}f(1, a = 2);", defined)), Err(CodegenError::ArgumentGivenTwice(..))));
+        assert!(matches!(slots(&format!("{}f(z = 2);", defined)), Err(CodegenError::UnknownNamedArgument(..))));
+        assert!(matches!(slots("g(a = 1);"), Err(CodegenError::NamedArgumentsToUnknownFunction(..))));
+    }
+}
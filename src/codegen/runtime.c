@@ -0,0 +1,251 @@
+/* Tong runtime, included at the top of every program the C backend emits */
+
+#include <math.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+typedef enum { TONG_NIL, TONG_INT, TONG_FLOAT, TONG_BOOL, TONG_STR } TongType;
+
+typedef struct {
+    TongType type;
+    union {
+        long long i;
+        double f;
+        int b;
+        const char *s;
+    } as;
+} Value;
+
+static inline Value tong_nil(void) { Value v; v.type = TONG_NIL; v.as.i = 0; return v; }
+static inline Value tong_int(long long i) { Value v; v.type = TONG_INT; v.as.i = i; return v; }
+static inline Value tong_float(double f) { Value v; v.type = TONG_FLOAT; v.as.f = f; return v; }
+static inline Value tong_bool(int b) { Value v; v.type = TONG_BOOL; v.as.b = b != 0; return v; }
+static inline Value tong_str(const char *s) { Value v; v.type = TONG_STR; v.as.s = s; return v; }
+
+static inline void tong_error(const char *message, const char *op) {
+    fprintf(stderr, "[!] [Runtime Error] %s '%s'\n", message, op);
+    exit(1);
+}
+
+static inline int tong_truthy(Value v) {
+    switch (v.type) {
+        case TONG_NIL: return 0;
+        case TONG_INT: return v.as.i != 0;
+        case TONG_FLOAT: return v.as.f != 0.0;
+        case TONG_BOOL: return v.as.b;
+        case TONG_STR: return v.as.s[0] != '\0';
+    }
+    return 0;
+}
+
+static inline int tong_is_number(Value v) { return v.type == TONG_INT || v.type == TONG_FLOAT; }
+static inline double tong_as_float(Value v) { return v.type == TONG_INT ? (double)v.as.i : v.as.f; }
+
+/* strings are never freed, programs are expected to be short lived */
+static inline const char *tong_to_string(Value v) {
+    char buffer[64];
+    switch (v.type) {
+        case TONG_NIL: return "nil";
+        case TONG_BOOL: return v.as.b ? "true" : "false";
+        case TONG_STR: return v.as.s;
+        case TONG_INT: snprintf(buffer, sizeof buffer, "%lld", v.as.i); break;
+        case TONG_FLOAT:
+            /* glibc prints the nan of 0.0 / 0.0 as -nan */
+            if (isnan(v.as.f)) return "nan";
+            snprintf(buffer, sizeof buffer, "%g", v.as.f);
+            break;
+    }
+    char *s = malloc(strlen(buffer) + 1);
+    strcpy(s, buffer);
+    return s;
+}
+
+static inline const char *tong_type_name(Value v) {
+    switch (v.type) {
+        case TONG_NIL: return "nil";
+        case TONG_INT: return "int";
+        case TONG_FLOAT: return "float";
+        case TONG_BOOL: return "bool";
+        case TONG_STR: return "str";
+    }
+    return "value";
+}
+
+/* Binary operations are passed the line of their operator, and errors in
+   one name its operands too: line 3: integer overflow in '2 ** 64' */
+static inline void tong_operation_error(int line, const char *message, Value a, const char *op, Value b) {
+    fprintf(stderr, "[!] [Runtime Error] line %d: %s '%s %s %s'\n",
+            line, message, tong_to_string(a), op, tong_to_string(b));
+    exit(1);
+}
+
+static inline void tong_type_error(int line, const char *op, Value a, Value b) {
+    fprintf(stderr, "[!] [Runtime Error] line %d: unsupported operand types for '%s': %s and %s\n",
+            line, op, tong_type_name(a), tong_type_name(b));
+    exit(1);
+}
+
+/* int op int stays an int, anything involving a float is a float. An int
+   divided by zero or a result that does not fit a long long is a runtime
+   error; float division follows IEEE 754 and gives inf or nan, which
+   is_inf and is_nan test for. */
+static inline Value tong_arith(const char *op, Value a, Value b, int line) {
+    if (!tong_is_number(a) || !tong_is_number(b)) {
+        tong_type_error(line, op, a, b);
+    }
+    if (a.type == TONG_INT && b.type == TONG_INT) {
+        long long x = a.as.i, y = b.as.i, result = 0;
+        int overflow = 0;
+        switch (op[0]) {
+            case '+': overflow = __builtin_add_overflow(x, y, &result); break;
+            case '-': overflow = __builtin_sub_overflow(x, y, &result); break;
+            case '*': overflow = __builtin_mul_overflow(x, y, &result); break;
+            case '/':
+            case '%':
+                if (y == 0) tong_operation_error(line, "integer division by zero in", a, op, b);
+                /* LLONG_MIN / -1 does not fit, LLONG_MIN % -1 is 0 */
+                if (y == -1) {
+                    if (op[0] == '%') return tong_int(0);
+                    overflow = __builtin_sub_overflow(0, x, &result);
+                    break;
+                }
+                return tong_int(op[0] == '/' ? x / y : x % y);
+        }
+        if (overflow) tong_operation_error(line, "integer overflow in", a, op, b);
+        return tong_int(result);
+    }
+    double x = tong_as_float(a), y = tong_as_float(b);
+    switch (op[0]) {
+        case '+': return tong_float(x + y);
+        case '-': return tong_float(x - y);
+        case '*': return tong_float(x * y);
+        case '/': return tong_float(x / y);
+        case '%': return tong_float(fmod(x, y));
+    }
+    tong_error("unknown operator", op);
+    return tong_nil();
+}
+
+static inline Value tong_add(Value a, Value b, int line) {
+    if (a.type == TONG_STR || b.type == TONG_STR) {
+        const char *x = tong_to_string(a), *y = tong_to_string(b);
+        char *s = malloc(strlen(x) + strlen(y) + 1);
+        strcpy(s, x);
+        strcat(s, y);
+        return tong_str(s);
+    }
+    return tong_arith("+", a, b, line);
+}
+
+static inline Value tong_sub(Value a, Value b, int line) { return tong_arith("-", a, b, line); }
+static inline Value tong_mul(Value a, Value b, int line) { return tong_arith("*", a, b, line); }
+static inline Value tong_div(Value a, Value b, int line) { return tong_arith("/", a, b, line); }
+static inline Value tong_mod(Value a, Value b, int line) { return tong_arith("%", a, b, line); }
+
+static inline Value tong_pow(Value a, Value b, int line) {
+    if (!tong_is_number(a) || !tong_is_number(b)) {
+        tong_type_error(line, "**", a, b);
+    }
+    /* exponentiation by squaring, the base is only squared while bits of
+       the exponent remain so squaring never overflows needlessly */
+    if (a.type == TONG_INT && b.type == TONG_INT && b.as.i >= 0) {
+        long long result = 1, base = a.as.i, exponent = b.as.i;
+        while (exponent > 0) {
+            if ((exponent & 1) && __builtin_mul_overflow(result, base, &result)) {
+                tong_operation_error(line, "integer overflow in", a, "**", b);
+            }
+            exponent >>= 1;
+            if (exponent > 0 && __builtin_mul_overflow(base, base, &base)) {
+                tong_operation_error(line, "integer overflow in", a, "**", b);
+            }
+        }
+        return tong_int(result);
+    }
+    return tong_float(pow(tong_as_float(a), tong_as_float(b)));
+}
+
+static inline Value tong_neg(Value a) {
+    if (a.type == TONG_INT) {
+        long long result;
+        if (__builtin_sub_overflow(0, a.as.i, &result)) {
+            char operation[32];
+            snprintf(operation, sizeof operation, "-(%lld)", a.as.i);
+            tong_error("integer overflow in", operation);
+        }
+        return tong_int(result);
+    }
+    if (a.type == TONG_FLOAT) return tong_float(-a.as.f);
+    fprintf(stderr, "[!] [Runtime Error] unsupported operand type for '-': %s\n", tong_type_name(a));
+    exit(1);
+}
+
+static inline Value tong_eq(Value a, Value b) {
+    if (tong_is_number(a) && tong_is_number(b)) {
+        if (a.type == TONG_INT && b.type == TONG_INT) return tong_bool(a.as.i == b.as.i);
+        return tong_bool(tong_as_float(a) == tong_as_float(b));
+    }
+    if (a.type != b.type) return tong_bool(0);
+    switch (a.type) {
+        case TONG_NIL: return tong_bool(1);
+        case TONG_BOOL: return tong_bool(a.as.b == b.as.b);
+        case TONG_STR: return tong_bool(strcmp(a.as.s, b.as.s) == 0);
+        default: return tong_bool(0);
+    }
+}
+
+/* <0, 0 or >0 like strcmp, for ints and strings. Floats are compared with
+   the C operators instead, so every comparison with nan is false. */
+static inline int tong_compare(const char *op, Value a, Value b, int line) {
+    if (a.type == TONG_INT && b.type == TONG_INT) return (a.as.i > b.as.i) - (a.as.i < b.as.i);
+    if (a.type == TONG_STR && b.type == TONG_STR) return strcmp(a.as.s, b.as.s);
+    tong_type_error(line, op, a, b);
+    return 0;
+}
+
+static inline int tong_is_float_pair(Value a, Value b) {
+    return tong_is_number(a) && tong_is_number(b) && (a.type == TONG_FLOAT || b.type == TONG_FLOAT);
+}
+
+static inline Value tong_lt(Value a, Value b, int line) {
+    if (tong_is_float_pair(a, b)) return tong_bool(tong_as_float(a) < tong_as_float(b));
+    return tong_bool(tong_compare("<", a, b, line) < 0);
+}
+static inline Value tong_gt(Value a, Value b, int line) {
+    if (tong_is_float_pair(a, b)) return tong_bool(tong_as_float(a) > tong_as_float(b));
+    return tong_bool(tong_compare(">", a, b, line) > 0);
+}
+static inline Value tong_le(Value a, Value b, int line) {
+    if (tong_is_float_pair(a, b)) return tong_bool(tong_as_float(a) <= tong_as_float(b));
+    return tong_bool(tong_compare("<=", a, b, line) <= 0);
+}
+static inline Value tong_ge(Value a, Value b, int line) {
+    if (tong_is_float_pair(a, b)) return tong_bool(tong_as_float(a) >= tong_as_float(b));
+    return tong_bool(tong_compare(">=", a, b, line) >= 0);
+}
+
+/* strings are the only containers the C runtime has */
+static inline Value tong_in(Value item, Value container, int line) {
+    if (item.type != TONG_STR || container.type != TONG_STR) {
+        tong_type_error(line, "in", item, container);
+    }
+    return tong_bool(strstr(container.as.s, item.as.s) != NULL);
+}
+
+/* is_nan and is_inf are false for anything that is not a float */
+static inline Value tong_builtin_is_nan(int argc, Value *argv) {
+    return tong_bool(argc > 0 && argv[0].type == TONG_FLOAT && isnan(argv[0].as.f));
+}
+
+static inline Value tong_builtin_is_inf(int argc, Value *argv) {
+    return tong_bool(argc > 0 && argv[0].type == TONG_FLOAT && isinf(argv[0].as.f));
+}
+
+/* print and out: arguments separated by spaces, then a newline */
+static inline Value tong_builtin_print(int argc, Value *argv) {
+    for (int i = 0; i < argc; i++) {
+        printf(i == 0 ? "%s" : " %s", tong_to_string(argv[i]));
+    }
+    printf("\n");
+    return tong_nil();
+}
@@ -0,0 +1,283 @@
+use crate::parser::ASTNode;
+
+// Graphviz digraph of a whole program, for `tong --ast=dot file.tong | dot -Tsvg`.
+// Nodes are labelled like the tree printer; ordering=out keeps children in
+// source order from left to right.
+pub fn emit(program: &[ASTNode]) -> String {
+    let mut writer = DotWriter {
+        out: String::new(),
+        next: 0,
+    };
+
+    writer.out.push_str("digraph AST {\n");
+    writer.out.push_str("    ordering=out;\n");
+    writer.out.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+
+    let root = writer.node("Program");
+    writer.children(root, program);
+
+    writer.out.push_str("}\n");
+    writer.out
+}
+
+struct DotWriter {
+    out: String,
+    next: usize,
+}
+
+impl DotWriter {
+
+    fn node(&mut self, label: &str) -> usize {
+        let id = self.next;
+        self.next += 1;
+        self.out.push_str(&format!("    n{} [label=\"{}\"];\n", id, escape(label)));
+        id
+    }
+
+    fn edge(&mut self, from: usize, to: usize) {
+        self.out.push_str(&format!("    n{} -> n{};\n", from, to));
+    }
+
+    fn child(&mut self, parent: usize, node: &ASTNode) {
+        let id = self.visit(node);
+        self.edge(parent, id);
+    }
+
+    fn children(&mut self, parent: usize, block: &[ASTNode]) {
+        for node in block {
+            self.child(parent, node);
+        }
+    }
+
+    // a labelled grouping node such as Then or Args holding a block
+    fn group(&mut self, parent: usize, label: &str, block: &[ASTNode]) -> usize {
+        let id = self.node(label);
+        self.edge(parent, id);
+        self.children(id, block);
+        id
+    }
+
+    fn visit(&mut self, node: &ASTNode) -> usize {
+        match node {
+            ASTNode::Eof => self.node("End of file."),
+
+            ASTNode::Integer(n) => self.node(&format!("Integer({})", n)),
+
+            ASTNode::Float(n) => self.node(&format!("Float({})", n)),
+
+            ASTNode::Identifier(iden) => self.node(&format!("Identifier({})", iden)),
+
+            ASTNode::BoolNode(b) => self.node(&format!("Bool({})", b)),
+
+            ASTNode::BreakNode => self.node("Break"),
+
+            ASTNode::ReturnNode(value) => {
+                let id = self.node("Return");
+                if let Some(value) = value {
+                    self.child(id, value);
+                }
+                id
+            }
+
+            ASTNode::StrLiteral(s) => self.node(&format!("StrLiteral(\"{}\")", s)),
+
+            ASTNode::UnaryOpNode { op, operand } => {
+                let id = self.node(&format!("UnaryOp('{}')", op));
+                self.child(id, operand);
+                id
+            }
+
+            ASTNode::BinOpNode { op, left, right, .. } => {
+                let id = self.node(&format!("BinOp('{}')", op));
+                self.child(id, left);
+                self.child(id, right);
+                id
+            }
+
+            ASTNode::ChainedCompareNode { operands, ops } => {
+                let ops: Vec<String> = ops.iter().map(|(op, _)| format!("'{}'", op)).collect();
+                let id = self.node(&format!("ChainedCompare({})", ops.join(", ")));
+                for operand in operands {
+                    self.child(id, operand);
+                }
+                id
+            }
+
+            ASTNode::VarDecNode { name, value, constant, public } => {
+                let kind = if *constant { "ConstDec" } else { "VarDec" };
+                let id = self.node(&format!("{}({}{})", kind, if *public { "pub " } else { "" }, name));
+                self.child(id, value);
+                id
+            }
+
+            ASTNode::AssignNode { name, value } => {
+                let id = self.node(&format!("Assign({})", name));
+                self.child(id, value);
+                id
+            }
+
+            ASTNode::IfElseNode { condition, then_branch, elif_branch, else_branch } => {
+                let id = self.node("If");
+                self.child(id, condition);
+                self.group(id, "Then", then_branch);
+                for (elif_condition, elif_block) in elif_branch {
+                    let elif = self.node("Elif");
+                    self.edge(id, elif);
+                    self.child(elif, elif_condition);
+                    self.children(elif, elif_block);
+                }
+                if let Some(else_block) = else_branch {
+                    self.group(id, "Else", else_block);
+                }
+                id
+            }
+
+            ASTNode::LoopNode { condition, block } => {
+                let id = self.node("Loop");
+                self.child(id, condition);
+                self.children(id, block);
+                id
+            }
+
+            ASTNode::DoWhileNode { block, condition } => {
+                let id = self.node("DoWhile");
+                self.children(id, block);
+                self.child(id, condition);
+                id
+            }
+
+            ASTNode::ParamNode { name, default, variadic } => {
+                let id = self.node(&format!("Param({}{})", if *variadic { "..." } else { "" }, name));
+                if let Some(default) = default {
+                    self.child(id, default);
+                }
+                id
+            }
+
+            ASTNode::SpreadNode(value) => {
+                let id = self.node("Spread");
+                self.child(id, value);
+                id
+            }
+
+            ASTNode::NamedArgNode { name, value } => {
+                let id = self.node(&format!("NamedArg({})", name));
+                self.child(id, value);
+                id
+            }
+
+            ASTNode::FuncCall { name, arguments } => {
+                let id = self.node(&format!("FuncCall({})", name));
+                self.children(id, arguments);
+                id
+            }
+
+            ASTNode::FuncDef { name, arguments, block, public } => {
+                let id = self.node(&format!("FuncDef({}{})", if *public { "pub " } else { "" }, name));
+                self.group(id, "Args", arguments);
+                self.children(id, block);
+                id
+            }
+
+            ASTNode::ImportNode { path, alias, names } => {
+                let id = self.node(&format!("Import({})", path));
+                if let Some(alias) = alias {
+                    let alias = self.node(&format!("As({})", alias));
+                    self.edge(id, alias);
+                }
+                for name in names {
+                    let name = self.node(&format!("Name({})", name));
+                    self.edge(id, name);
+                }
+                id
+            }
+
+            ASTNode::ClassDef { name, parent, fields, methods } => {
+                let id = match parent {
+                    Some(parent) => self.node(&format!("Class({} : {})", name, parent)),
+                    None => self.node(&format!("Class({})", name)),
+                };
+                self.children(id, fields);
+                self.children(id, methods);
+                id
+            }
+
+            ASTNode::MethodCall { object, method, arguments, optional } => {
+                let id = self.node(&format!("MethodCall({}{})", if *optional { "?." } else { "." }, method));
+                self.child(id, object);
+                self.children(id, arguments);
+                id
+            }
+
+            ASTNode::FieldAssignNode { object, field, value } => {
+                let id = self.node(&format!("AssignField(.{})", field));
+                self.child(id, object);
+                self.child(id, value);
+                id
+            }
+
+            ASTNode::MemberNode { object, field, optional } => {
+                let id = self.node(&format!("Member({}{})", if *optional { "?." } else { "." }, field));
+                self.child(id, object);
+                id
+            }
+
+            ASTNode::TupleNode(items) => {
+                let id = self.node("Tuple");
+                self.children(id, items);
+                id
+            }
+
+            ASTNode::DestructureNode { pattern, value, constant, declare } => {
+                let kind = match (declare, constant) {
+                    (true, true) => "ConstDec",
+                    (true, false) => "VarDec",
+                    (false, _) => "Assign",
+                };
+                let id = self.node(&format!("{}({})", kind, pattern));
+                self.child(id, value);
+                id
+            }
+        }
+    }
+}
+
+// labels are double quoted DOT strings
+fn escape(label: &str) -> String {
+    let mut out = String::new();
+    for c in label.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::tests::parse;
+
+    #[test]
+    fn program_becomes_a_digraph_with_escaped_labels() {
+        let dot = emit(&parse("let s = \"a\\\\b\" + 1;").unwrap());
+        assert_eq!(dot, "digraph AST {
+    ordering=out;
+    node [shape=box, fontname=\"monospace\"];
+    n0 [label=\"Program\"];
+    n1 [label=\"VarDec(s)\"];
+    n2 [label=\"BinOp('+')\"];
+    n3 [label=\"StrLiteral(\\\"a\\\\b\\\")\"];
+    n2 -> n3;
+    n4 [label=\"Integer(1)\"];
+    n2 -> n4;
+    n1 -> n2;
+    n0 -> n1;
+}
+");
+    }
+}
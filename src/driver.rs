@@ -0,0 +1,106 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::suggest;
+
+#[derive(Debug)]
+pub enum DriverError {
+    NotFound(String, Option<String>),
+    PermissionDenied(String),
+    NotUtf8(String, usize),
+    Io(String, io::Error),
+}
+
+impl DriverError {
+    // the file that could not be read, errors have no position inside it
+    pub fn path(&self) -> &str {
+        match self {
+            DriverError::NotFound(path, _)
+            | DriverError::PermissionDenied(path)
+            | DriverError::NotUtf8(path, _)
+            | DriverError::Io(path, _) => path,
+        }
+    }
+}
+
+impl fmt::Display for DriverError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DriverError::NotFound(..) => write!(f, "file not found"),
+            DriverError::PermissionDenied(_) => write!(f, "permission denied"),
+            DriverError::NotUtf8(_, offset) => write!(f, "not valid UTF-8 (invalid byte at offset {})", offset),
+            DriverError::Io(_, e) => write!(f, "{}", e),
+        }
+    }
+}
+
+pub fn read_source(filename: &str) -> Result<String, DriverError> {
+    let bytes = match fs::read(filename) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            return Err(match e.kind() {
+                io::ErrorKind::NotFound => DriverError::NotFound(filename.to_string(), suggest_file(filename)),
+                io::ErrorKind::PermissionDenied => DriverError::PermissionDenied(filename.to_string()),
+                _ => DriverError::Io(filename.to_string(), e),
+            });
+        }
+    };
+
+    String::from_utf8(bytes)
+        .map_err(|e| DriverError::NotUtf8(filename.to_string(), e.utf8_error().valid_up_to()))
+}
+
+// looks for the closest file name in the same directory, so a typo like
+// `tset.tag` points the user at `test.tag`
+fn suggest_file(filename: &str) -> Option<String> {
+    let path = Path::new(filename);
+    let wanted = path.file_name()?.to_str()?;
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    let names: Vec<String> = fs::read_dir(dir).ok()?
+        .flatten()
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+
+    let name = suggest::closest(wanted, names.iter().map(String::as_str))?;
+    Some(dir.join(name).display().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_file_suggests_a_neighbour() {
+        let dir = std::env::temp_dir().join(format!("tong_driver_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("test.tong"), "").unwrap();
+        let missing = dir.join("tset.tong").display().to_string();
+        let result = read_source(&missing);
+        fs::remove_dir_all(&dir).unwrap();
+        match result {
+            Err(e @ DriverError::NotFound(_, Some(_))) => {
+                assert_eq!(e.path(), missing);
+                assert_eq!(e.to_string(), "file not found");
+                if let DriverError::NotFound(_, Some(suggestion)) = e {
+                    assert!(suggestion.ends_with("test.tong"));
+                }
+            }
+            other => panic!("expected a suggestion, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn invalid_utf8_names_the_offset() {
+        let path = std::env::temp_dir().join(format!("tong_driver_utf8_{}.tong", std::process::id()));
+        fs::write(&path, b"let x = \xff;").unwrap();
+        let result = read_source(&path.display().to_string());
+        fs::remove_file(&path).unwrap();
+        assert_eq!(result.unwrap_err().to_string(), "not valid UTF-8 (invalid byte at offset 8)");
+    }
+}
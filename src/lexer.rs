@@ -1,199 +1,734 @@
-#[derive(Clone, Debug, PartialEq)]
+use std::borrow::Cow;
+use std::fmt;
+use std::num::IntErrorKind;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum TokenType {
-    Num, Add, Sub, Div, Mul, Dot, True, Pub,
+    Num, Flt, Add, Sub, Div, Mul, Dot, True, Pub,
     Opt, Cpt, Ocl, Ccl, Scln, Equ, False, Eof,
     Eqv, Gre, Les, Geq, Leq, Break, Str, Mod,
     Loop, If, Elif, Else, Func, Slash, Return,
-    Iden, Qt, And, Or, Let, NewLine, Com, Import
+    Iden, Qt, And, Or, Let, NewLine, Com, Import,
+    Pow, Spread, Const, Osq, Csq, Coalesce, OptChain, In,
+    Do, While, Class, Colon
 }
 
-#[derive(Debug)]
-pub struct Token {
+// Token values borrow from the source buffer. Only literals whose value
+// differs from their spelling (escapes, joined strings, normalized numbers)
+// own their text.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Token<'src> {
     pub ttype: TokenType,
-    pub value: String,
+    pub value: Cow<'src, str>,
     pub line_num: u32,
+    // byte offsets of the first character and just past the last one,
+    // see source::SourceManager
+    pub offset: usize,
+    pub end: usize,
 }
 
-pub fn lex(file_buffer: &str, pos: &mut usize, line_number: &mut u32) -> Option<Token> {
-    let chars: Vec<char> = file_buffer.chars().collect();
+impl Token<'_> {
+    // for callers that need tokens to outlive the source buffer
+    #[allow(dead_code)]
+    pub fn into_owned(self) -> Token<'static> {
+        Token {
+            ttype: self.ttype,
+            value: Cow::Owned(self.value.into_owned()),
+            line_num: self.line_num,
+            offset: self.offset,
+            end: self.end,
+        }
+    }
+}
 
-    while *pos < chars.len() {
-        if chars[*pos] == '#' {
-            while *pos < chars.len() && chars[*pos] != '\n' {
-                *pos += 1;
-            }
-            continue;
+// Like parser errors, lexer errors carry the byte offset they point at and
+// the driver turns it into file:line:col
+#[derive(Debug)]
+pub enum LexerError {
+    UnknownCharacter(char, usize),
+    UnterminatedString(usize),
+    InvalidEscape(char, usize),
+    ExpectedUnicodeBrace(usize),
+    UnterminatedUnicodeEscape(String, usize),
+    InvalidUnicodeEscape(String, usize),
+    // (prefix, offset) for a bare 0x, 0b or 0o
+    ExpectedDigits(String, usize),
+    InvalidNumber(String, usize),
+    ExpectedExponent(String, usize),
+    IntegerTooLarge(String, usize),
+    MisplacedUnderscore(String, usize),
+}
+
+impl LexerError {
+    pub fn offset(&self) -> usize {
+        match self {
+            LexerError::UnknownCharacter(_, offset)
+            | LexerError::UnterminatedString(offset)
+            | LexerError::InvalidEscape(_, offset)
+            | LexerError::ExpectedUnicodeBrace(offset)
+            | LexerError::UnterminatedUnicodeEscape(_, offset)
+            | LexerError::InvalidUnicodeEscape(_, offset)
+            | LexerError::ExpectedDigits(_, offset)
+            | LexerError::InvalidNumber(_, offset)
+            | LexerError::ExpectedExponent(_, offset)
+            | LexerError::IntegerTooLarge(_, offset)
+            | LexerError::MisplacedUnderscore(_, offset) => *offset,
         }
+    }
+}
 
-        if chars[*pos].is_whitespace() {
-            if chars[*pos] == '\n' {
-                *line_number += 1;
-            }
-            *pos += 1;
-            continue;
+impl fmt::Display for LexerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LexerError::UnknownCharacter(c, _) => write!(f, "unknown character '{}'", c),
+            LexerError::UnterminatedString(_) => write!(f, "unterminated string"),
+            LexerError::InvalidEscape(c, _) => write!(f, "invalid escape sequence '\\{}'", c),
+            LexerError::ExpectedUnicodeBrace(_) => write!(f, "expected '{{' after '\\u'"),
+            LexerError::UnterminatedUnicodeEscape(digits, _) => write!(f, "unterminated unicode escape '\\u{{{}'", digits),
+            LexerError::InvalidUnicodeEscape(digits, _) => write!(f, "invalid unicode escape '\\u{{{}}}'", digits),
+            LexerError::ExpectedDigits(prefix, _) => write!(f, "expected digits after '{}'", prefix),
+            LexerError::InvalidNumber(literal, _) => write!(f, "invalid number literal '{}'", literal),
+            LexerError::ExpectedExponent(literal, _) => write!(f, "expected exponent digits in '{}'", literal),
+            LexerError::IntegerTooLarge(literal, _) => write!(f, "integer literal '{}' is too large", literal),
+            LexerError::MisplacedUnderscore(literal, _) => write!(f, "misplaced '_' in number literal '{}'", literal),
         }
+    }
+}
 
-        let tok = match chars[*pos] {
-            '+' => {
-                *pos += 1;
-                Some(Token { ttype: TokenType::Add, value: "+".to_string(), line_num: *line_number})
-            }
-            '-' => {
-                *pos += 1;
-                Some(Token { ttype: TokenType::Sub, value: "-".to_string(), line_num: *line_number})
-            }
-            '*' => {
-                *pos += 1;
-                Some(Token { ttype: TokenType::Mul, value: "*".to_string(), line_num: *line_number})
-            }
-            '/' => {
+// pos is a byte offset into file_buffer and always sits on a char boundary
+pub fn lex<'src>(file_buffer: &'src str, pos: &mut usize, line_number: &mut u32) -> Result<Token<'src>, LexerError> {
+    let bytes = file_buffer.as_bytes();
+
+    skip_trivia(file_buffer, pos, line_number);
+    if *pos >= bytes.len() {
+        return Ok(Token {
+            ttype: TokenType::Eof,
+            value: Cow::Borrowed(""),
+            line_num: *line_number,
+            offset: *pos,
+            end: *pos});
+    }
+
+    let c = char_at(file_buffer, *pos);
+    let offset = *pos;
+
+    if bytes[*pos..].starts_with(b"r\"") {
+        let start_line = *line_number;
+        let literal = lex_raw_string(file_buffer, pos, line_number)?;
+        let literal = lex_adjacent_strings(file_buffer, pos, line_number, literal)?;
+        return Ok(Token { ttype: TokenType::Str, value: literal, line_num: start_line, offset, end: *pos});
+    }
+
+    let tok = match c {
+        '+' => {
+            *pos += 1;
+            Some(Token { ttype: TokenType::Add, value: Cow::Borrowed("+"), line_num: *line_number, offset, end: *pos})
+        }
+        '-' => {
+            *pos += 1;
+            Some(Token { ttype: TokenType::Sub, value: Cow::Borrowed("-"), line_num: *line_number, offset, end: *pos})
+        }
+        '*' => {
+            *pos += 1;
+            if *pos < bytes.len() && bytes[*pos] == b'*' {
                 *pos += 1;
-                Some(Token { ttype: TokenType::Div, value: "/".to_string(), line_num: *line_number})
+                Some(Token { ttype: TokenType::Pow, value: Cow::Borrowed("**"), line_num: *line_number, offset, end: *pos})
+            } else {
+                Some(Token { ttype: TokenType::Mul, value: Cow::Borrowed("*"), line_num: *line_number, offset, end: *pos})
             }
-            '%' => {
+        }
+        '/' => {
+            *pos += 1;
+            Some(Token { ttype: TokenType::Div, value: Cow::Borrowed("/"), line_num: *line_number, offset, end: *pos})
+        }
+        '%' => {
+            *pos += 1;
+            Some(Token { ttype: TokenType::Mod, value: Cow::Borrowed("%"), line_num: *line_number, offset, end: *pos})
+        }
+        '(' => {
+            *pos += 1;
+            Some(Token { ttype: TokenType::Opt, value: Cow::Borrowed("("), line_num: *line_number, offset, end: *pos})
+        }
+        ')' => {
+            *pos += 1;
+            Some(Token { ttype: TokenType::Cpt, value: Cow::Borrowed(")"), line_num: *line_number, offset, end: *pos})
+        }
+        '{' => {
+            *pos += 1;
+            Some(Token { ttype: TokenType::Ocl, value: Cow::Borrowed("{"), line_num: *line_number, offset, end: *pos})
+        }
+        '}' => {
+            *pos += 1;
+            Some(Token { ttype: TokenType::Ccl, value: Cow::Borrowed("}"), line_num: *line_number, offset, end: *pos})
+        }
+        '[' => {
+            *pos += 1;
+            Some(Token { ttype: TokenType::Osq, value: Cow::Borrowed("["), line_num: *line_number, offset, end: *pos})
+        }
+        ']' => {
+            *pos += 1;
+            Some(Token { ttype: TokenType::Csq, value: Cow::Borrowed("]"), line_num: *line_number, offset, end: *pos})
+        }
+        '.' => {
+            if bytes[*pos..].starts_with(b"...") {
+                *pos += 3;
+                Some(Token { ttype: TokenType::Spread, value: Cow::Borrowed("..."), line_num: *line_number, offset, end: *pos})
+            } else {
                 *pos += 1;
-                Some(Token { ttype: TokenType::Mod, value: "%".to_string(), line_num: *line_number})
+                Some(Token { ttype: TokenType::Dot, value: Cow::Borrowed("."), line_num: *line_number, offset, end: *pos})
             }
-            '(' => {
+        }
+        ':' => {
+            *pos += 1;
+            Some(Token { ttype: TokenType::Colon, value: Cow::Borrowed(":"), line_num: *line_number, offset, end: *pos})
+        }
+        ',' => {
+            *pos += 1;
+            Some(Token { ttype: TokenType::Com, value: Cow::Borrowed(","), line_num: *line_number, offset, end: *pos})
+        }
+        ';' => {
+            *pos += 1;
+            Some(Token { ttype: TokenType::Scln, value: Cow::Borrowed(";"), line_num: *line_number, offset, end: *pos})
+        }
+        '\"' => {
+            let start_line = *line_number;
+            let literal = lex_string(file_buffer, pos, line_number)?;
+            let literal = lex_adjacent_strings(file_buffer, pos, line_number, literal)?;
+            return Ok(Token { ttype: TokenType::Str, value: literal, line_num: start_line, offset, end: *pos});
+        }
+        '=' => {
+            *pos += 1;
+            if *pos < bytes.len() && bytes[*pos] == b'=' {
                 *pos += 1;
-                Some(Token { ttype: TokenType::Opt, value: "(".to_string(), line_num: *line_number})
+                Some(Token { ttype: TokenType::Eqv, value: Cow::Borrowed("=="), line_num: *line_number, offset, end: *pos})
+            } else {
+                Some(Token { ttype: TokenType::Equ, value: Cow::Borrowed("="), line_num: *line_number, offset, end: *pos})
             }
-            ')' => {
+        }
+        '<' => {
+            *pos += 1;
+            if *pos < bytes.len() && bytes[*pos] == b'=' {
                 *pos += 1;
-                Some(Token { ttype: TokenType::Cpt, value: ")".to_string(), line_num: *line_number})
+                Some(Token { ttype: TokenType::Leq, value: Cow::Borrowed("<="), line_num: *line_number, offset, end: *pos})
+            } else {
+                Some(Token { ttype: TokenType::Les, value: Cow::Borrowed("<"), line_num: *line_number, offset, end: *pos})
             }
-            '{' => {
+        }
+        '>' => {
+            *pos += 1;
+            if *pos < bytes.len() && bytes[*pos] == b'=' {
                 *pos += 1;
-                Some(Token { ttype: TokenType::Ocl, value: "{".to_string(), line_num: *line_number})
+                Some(Token { ttype: TokenType::Geq, value: Cow::Borrowed(">="), line_num: *line_number, offset, end: *pos})
+            } else {
+                Some(Token { ttype: TokenType::Gre, value: Cow::Borrowed(">"), line_num: *line_number, offset, end: *pos})
             }
-            '}' => {
+        }
+        // a lone ? is not a token and falls through to the error below
+        '?' if bytes[*pos..].starts_with(b"??") => {
+            *pos += 2;
+            Some(Token { ttype: TokenType::Coalesce, value: Cow::Borrowed("??"), line_num: *line_number, offset, end: *pos})
+        }
+        '?' if bytes[*pos..].starts_with(b"?.") => {
+            *pos += 2;
+            Some(Token { ttype: TokenType::OptChain, value: Cow::Borrowed("?."), line_num: *line_number, offset, end: *pos})
+        }
+        _ => None,
+    };
+
+    if let Some(tok) = tok {
+        return Ok(tok);
+    }
+
+    if c.is_ascii_digit() {
+        lex_number(file_buffer, pos, *line_number)
+    } else if c.is_ascii_alphabetic() || c == '_' {
+        let start = *pos;
+        *pos += 1;
+        while *pos < bytes.len() && (bytes[*pos].is_ascii_alphanumeric() || bytes[*pos] == b'_') {
+            *pos += 1;
+        }
+        let val = &file_buffer[start..*pos];
+        let token_type = match val {
+            "loop" => TokenType::Loop,
+            "do" => TokenType::Do,
+            "while" => TokenType::While,
+            "class" => TokenType::Class,
+            "if" => TokenType::If,
+            "elif" => TokenType::Elif,
+            "else" => TokenType::Else,
+            "true" => TokenType::True,
+            "false" => TokenType::False,
+            "break" => TokenType::Break,
+            "return" => TokenType::Return,
+            "import" => TokenType::Import,
+            "pub" => TokenType::Pub,
+            "fn" => TokenType::Func,
+            "and" => TokenType::And,
+            "or" => TokenType::Or,
+            "in" => TokenType::In,
+            "let" => TokenType::Let,
+            "const" => TokenType::Const,
+            _ => TokenType::Iden,
+        };
+        Ok(Token { ttype: token_type, value: Cow::Borrowed(val), line_num: *line_number, offset, end: *pos})
+    } else {
+        Err(LexerError::UnknownCharacter(c, offset))
+    }
+}
+
+// the whole file up to and including Eof. Lexing stops at the first error,
+// the parser never sees a partial stream.
+pub fn tokenize(source: &str) -> Result<Vec<Token<'_>>, LexerError> {
+    let mut pos = 0;
+    let mut line_number = 1;
+    let mut tokens = Vec::new();
+    loop {
+        let tok = lex(source, &mut pos, &mut line_number)?;
+        if tok.ttype == TokenType::Eof {
+            tokens.push(tok);
+            return Ok(tokens);
+        }
+        tokens.push(tok);
+    }
+}
+
+fn char_at(file_buffer: &str, pos: usize) -> char {
+    file_buffer[pos..].chars().next().unwrap_or('\0')
+}
+
+// whitespace and # comments up to the next token
+fn skip_trivia(file_buffer: &str, pos: &mut usize, line_number: &mut u32) {
+    let bytes = file_buffer.as_bytes();
+    while *pos < bytes.len() {
+        if bytes[*pos] == b'#' {
+            while *pos < bytes.len() && bytes[*pos] != b'\n' {
                 *pos += 1;
-                Some(Token { ttype: TokenType::Ccl, value: "}".to_string(), line_num: *line_number})
             }
-            ',' => {
-                *pos += 1;
-                Some(Token { ttype: TokenType::Com, value: ",".to_string(), line_num: *line_number})
+            continue;
+        }
+
+        let c = char_at(file_buffer, *pos);
+        if !c.is_whitespace() {
+            return;
+        }
+        if c == '\n' {
+            *line_number += 1;
+        }
+        *pos += c.len_utf8();
+    }
+}
+
+// "a" "b" lexes as a single "ab" literal so long strings can be split
+// across lines, with comments between the pieces if need be. Raw and
+// triple-quoted pieces join in too.
+fn lex_adjacent_strings<'src>(file_buffer: &'src str, pos: &mut usize, line_number: &mut u32, mut literal: Cow<'src, str>) -> Result<Cow<'src, str>, LexerError> {
+    loop {
+        let mut next = *pos;
+        let mut next_line = *line_number;
+        skip_trivia(file_buffer, &mut next, &mut next_line);
+        let raw = file_buffer[next..].starts_with("r\"");
+        if !raw && !file_buffer[next..].starts_with('"') {
+            return Ok(literal);
+        }
+        *pos = next;
+        *line_number = next_line;
+        let piece = if raw {
+            lex_raw_string(file_buffer, pos, line_number)?
+        } else {
+            lex_string(file_buffer, pos, line_number)?
+        };
+        literal.to_mut().push_str(&piece);
+    }
+}
+
+
+// r"..." raw strings take backslashes literally
+fn lex_raw_string<'src>(file_buffer: &'src str, pos: &mut usize, line_number: &mut u32) -> Result<Cow<'src, str>, LexerError> {
+    *pos += 2; // r and the opening quote
+    lex_string_body(file_buffer, pos, line_number, "\"", true)
+}
+
+fn lex_string<'src>(file_buffer: &'src str, pos: &mut usize, line_number: &mut u32) -> Result<Cow<'src, str>, LexerError> {
+    if file_buffer[*pos..].starts_with("\"\"\"") {
+        return lex_triple_string(file_buffer, pos, line_number);
+    }
+
+    *pos += 1; // opening quote
+    lex_string_body(file_buffer, pos, line_number, "\"", false)
+}
+
+// """...""" may span lines. When the opening quotes end their line that
+// newline is dropped and the common indentation of the body is stripped,
+// so the literal can be indented along with the surrounding code.
+fn lex_triple_string<'src>(file_buffer: &'src str, pos: &mut usize, line_number: &mut u32) -> Result<Cow<'src, str>, LexerError> {
+    *pos += 3; // opening quotes
+    let newline = ["\n", "\r\n"].into_iter().find(|newline| file_buffer[*pos..].starts_with(newline));
+
+    let literal = lex_string_body(file_buffer, pos, line_number, "\"\"\"", false)?;
+
+    if let Some(newline) = newline {
+        return Ok(Cow::Owned(strip_indent(&literal[newline.len()..])));
+    }
+    Ok(literal)
+}
+
+// Reads up to and including the closing delimiter. The literal borrows from
+// the source until the first escape forces a copy.
+fn lex_string_body<'src>(file_buffer: &'src str, pos: &mut usize, line_number: &mut u32, closing: &str, raw: bool) -> Result<Cow<'src, str>, LexerError> {
+    let bytes = file_buffer.as_bytes();
+    let start = *pos;
+    let mut owned: Option<String> = None;
+
+    loop {
+        if *pos >= bytes.len() || (!raw && bytes[*pos] == b'\\' && *pos + 1 >= bytes.len()) {
+            // reported at the opening quotes, or the r of a raw string
+            return Err(LexerError::UnterminatedString(start - closing.len() - raw as usize));
+        }
+        if file_buffer[*pos..].starts_with(closing) {
+            break;
+        }
+        if !raw && bytes[*pos] == b'\\' {
+            let literal = owned.get_or_insert_with(|| file_buffer[start..*pos].to_string());
+            *pos += 1;
+            literal.push(lex_escape(file_buffer, pos)?);
+            continue;
+        }
+
+        let c = char_at(file_buffer, *pos);
+        if c == '\n' {
+            *line_number += 1;
+        }
+        if let Some(literal) = owned.as_mut() {
+            literal.push(c);
+        }
+        *pos += c.len_utf8();
+    }
+
+    let end = *pos;
+    *pos += closing.len();
+    match owned {
+        Some(literal) => Ok(Cow::Owned(literal)),
+        None => Ok(Cow::Borrowed(&file_buffer[start..end])),
+    }
+}
+
+fn strip_indent(text: &str) -> String {
+    let mut lines: Vec<&str> = text.split('\n').collect();
+
+    // the closing quotes sit on their own line, keep the newline before them
+    // but not the indentation
+    if lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+        lines.push("");
+    }
+
+    // the longest run of spaces and tabs every line with text starts with,
+    // other whitespace such as U+00A0 is part of the text
+    let mut indent: Option<&str> = None;
+    for line in lines.iter().filter(|line| !line.trim().is_empty()) {
+        let run = &line[..line.len() - line.trim_start_matches([' ', '\t']).len()];
+        indent = Some(match indent {
+            Some(indent) => {
+                let common = indent.bytes().zip(run.bytes()).take_while(|(a, b)| a == b).count();
+                &indent[..common]
             }
-            ';' => {
+            None => run,
+        });
+    }
+    let indent = indent.unwrap_or("");
+
+    lines.iter()
+        .map(|line| line.strip_prefix(indent).unwrap_or_else(|| line.trim_start_matches([' ', '\t'])))
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+// pos points just past the backslash
+fn lex_escape(file_buffer: &str, pos: &mut usize) -> Result<char, LexerError> {
+    let offset = *pos - 1;
+    let c = match char_at(file_buffer, *pos) {
+        '\"' => '\"',
+        'n' => '\n',
+        '\\' => '\\',
+        '\'' => '\'',
+        't' => '\t',
+        'r' => '\r',
+        '0' => '\0',
+        'u' => {
+            *pos += 1;
+            return lex_unicode_escape(file_buffer, pos, offset);
+        }
+        c => return Err(LexerError::InvalidEscape(c, offset)),
+    };
+    *pos += 1;
+    Ok(c)
+}
+
+// \u{XXXX}: one to six hex digits naming a unicode scalar value
+// offset is that of the backslash
+fn lex_unicode_escape(file_buffer: &str, pos: &mut usize, offset: usize) -> Result<char, LexerError> {
+    let bytes = file_buffer.as_bytes();
+    if *pos >= bytes.len() || bytes[*pos] != b'{' {
+        return Err(LexerError::ExpectedUnicodeBrace(offset));
+    }
+    *pos += 1;
+
+    let start = *pos;
+    while *pos < bytes.len() && bytes[*pos] != b'}' && bytes[*pos] != b'"' {
+        *pos += 1;
+    }
+    let digits = &file_buffer[start..*pos];
+    if *pos >= bytes.len() || bytes[*pos] != b'}' {
+        return Err(LexerError::UnterminatedUnicodeEscape(digits.to_string(), offset));
+    }
+    *pos += 1;
+
+    let code = if (1..=6).contains(&digits.len()) {
+        u32::from_str_radix(digits, 16).ok().and_then(char::from_u32)
+    } else {
+        None
+    };
+    code.ok_or_else(|| LexerError::InvalidUnicodeEscape(digits.to_string(), offset))
+}
+
+fn lex_number<'src>(file_buffer: &'src str, pos: &mut usize, line_number: u32) -> Result<Token<'src>, LexerError> {
+    let bytes = file_buffer.as_bytes();
+    let offset = *pos;
+
+    // 0x, 0b and 0o prefixed integers are normalized to their decimal value
+    // so the parser only ever has to deal with plain numbers
+    if bytes[*pos] == b'0' && *pos + 1 < bytes.len() {
+        let radix = match bytes[*pos + 1] {
+            b'x' | b'X' => Some(16),
+            b'b' | b'B' => Some(2),
+            b'o' | b'O' => Some(8),
+            _ => None,
+        };
+
+        if let Some(radix) = radix {
+            let prefix = &file_buffer[*pos..*pos + 2];
+            *pos += 2;
+            let start = *pos;
+            while *pos < bytes.len() && (bytes[*pos].is_ascii_alphanumeric() || bytes[*pos] == b'_') {
                 *pos += 1;
-                Some(Token { ttype: TokenType::Scln, value: ";".to_string(), line_num: *line_number})
             }
-            '\"' => {
-                *pos += 1;
-                let mut literal = String::new();
-                while *pos < chars.len() && chars[*pos] != '\"' {
-                    if chars[*pos] == '\\' {
-                        *pos += 1;
-                        if *pos >= chars.len() {
-                            return None;
-                        }
-                        match chars[*pos] {
-                            '\"' => {
-                                *pos += 1;
-                                literal.push('\"');
-                            }
-                            'n' => {
-                                *pos += 1;
-                                literal.push('\n');
-                            }
-                            '\\' => {
-                                *pos += 1;
-                                literal.push('\\');
-                            }
-                            _ => return None,
-                        }
-                    } else {
-                        literal.push(chars[*pos]);
-                        *pos += 1;
-                    }
-                }
-                *pos += 1;
-                return Some(Token { ttype: TokenType::Str, value: literal, line_num: *line_number});
+            let digits = &file_buffer[start..*pos];
+            if digits.is_empty() {
+                return Err(LexerError::ExpectedDigits(prefix.to_string(), offset));
             }
-            '=' => {
-                *pos += 1;
-                if *pos < chars.len() && chars[*pos] == '=' {
-                    *pos += 1;
-                    Some(Token { ttype: TokenType::Eqv, value: "==".to_string(), line_num: *line_number})
-                } else {
-                    Some(Token { ttype: TokenType::Equ, value: "=".to_string(), line_num: *line_number})
+            let digits = strip_underscores(digits, radix, offset)?;
+            return match i64::from_str_radix(&digits, radix) {
+                Ok(n) => Ok(Token { ttype: TokenType::Num, value: Cow::Owned(n.to_string()), line_num: line_number, offset, end: *pos}),
+                Err(e) if *e.kind() == IntErrorKind::PosOverflow => {
+                    Err(LexerError::IntegerTooLarge(format!("{}{}", prefix, digits), offset))
                 }
-            }
-            '<' => {
-                *pos += 1;
-                if *pos < chars.len() && chars[*pos] == '=' {
-                    *pos += 1;
-                    Some(Token { ttype: TokenType::Leq, value: "<=".to_string(), line_num: *line_number})
-                } else {
-                    Some(Token { ttype: TokenType::Les, value: "<".to_string(), line_num: *line_number})
+                Err(_) => Err(LexerError::InvalidNumber(format!("{}{}", prefix, digits), offset)),
+            };
+        }
+    }
+
+    let start = *pos;
+    let mut float = false;
+    while *pos < bytes.len() && (bytes[*pos].is_ascii_digit() || bytes[*pos] == b'.' || bytes[*pos] == b'_') {
+        if bytes[*pos] == b'.' {
+            if float {
+                // 1.2.3
+                let mut end = *pos;
+                while end < bytes.len() && (bytes[end].is_ascii_digit() || bytes[end] == b'.' || bytes[end] == b'_') {
+                    end += 1;
                 }
+                return Err(LexerError::InvalidNumber(file_buffer[start..end].to_string(), offset));
             }
-            '>' => {
+            float = true;
+        }
+        *pos += 1;
+    }
+
+    // scientific notation: 1.5e3, 2E-4
+    if *pos < bytes.len() && (bytes[*pos] == b'e' || bytes[*pos] == b'E') {
+        let mut exp = *pos + 1;
+        if exp < bytes.len() && (bytes[exp] == b'+' || bytes[exp] == b'-') {
+            exp += 1;
+        }
+        if exp < bytes.len() && bytes[exp].is_ascii_digit() {
+            *pos = exp;
+            while *pos < bytes.len() && (bytes[*pos].is_ascii_digit() || bytes[*pos] == b'_') {
                 *pos += 1;
-                if *pos < chars.len() && chars[*pos] == '=' {
-                    *pos += 1;
-                    Some(Token { ttype: TokenType::Geq, value: ">=".to_string(), line_num: *line_number})
-                } else {
-                    Some(Token { ttype: TokenType::Gre, value: ">".to_string(), line_num: *line_number})
-                }
             }
-            _ => None,
-        };
+        } else {
+            return Err(LexerError::ExpectedExponent(file_buffer[start..=*pos].to_string(), offset));
+        }
+    }
+
+    let val = strip_underscores(&file_buffer[start..*pos], 10, offset)?;
+
+    // anything with a fraction or an exponent is a float, the rest must fit an i64
+    if float || val.contains(['e', 'E']) {
+        return Ok(Token { ttype: TokenType::Flt, value: val, line_num: line_number, offset, end: *pos});
+    }
+    if val.parse::<i64>().is_err() {
+        return Err(LexerError::IntegerTooLarge(val.into_owned(), offset));
+    }
+    Ok(Token { ttype: TokenType::Num, value: val, line_num: line_number, offset, end: *pos})
+}
+
+// underscores are only allowed between two digits: 1_000 is fine,
+// 1_, 1_.5 and 0x_FF are not
+// offset is that of the whole literal
+fn strip_underscores(raw: &str, radix: u32, offset: usize) -> Result<Cow<'_, str>, LexerError> {
+    if !raw.contains('_') {
+        return Ok(Cow::Borrowed(raw));
+    }
 
-        if let Some(tok) = tok {
-            return Some(tok);
+    let chars: Vec<char> = raw.chars().collect();
+    for (i, c) in chars.iter().enumerate() {
+        if *c != '_' {
+            continue;
         }
+        let before = i > 0 && (chars[i - 1].is_digit(radix) || chars[i - 1] == '_');
+        let after = i + 1 < chars.len() && (chars[i + 1].is_digit(radix) || chars[i + 1] == '_');
+        if !before || !after {
+            return Err(LexerError::MisplacedUnderscore(raw.to_string(), offset));
+        }
+    }
+    Ok(Cow::Owned(raw.replace('_', "")))
+}
 
-        if chars[*pos].is_ascii_digit() {
-            let mut val = String::new();
-            let mut float = false;
-            while *pos < chars.len() && (chars[*pos].is_ascii_digit() || chars[*pos] == '.') {
-                if chars[*pos] == '.' {
-                    if float {
-                        return None;
-                    }
-                    float = true;
-                }
-                val.push(chars[*pos]);
-                *pos += 1;
-            }
-            return Some(Token { ttype: TokenType::Num, value: val, line_num: *line_number});
-        } else if chars[*pos].is_ascii_alphabetic() || chars[*pos] == '_' {
-            let mut val = String::new();
-            val.push(chars[*pos]);
-            *pos += 1;
-            while *pos < chars.len() && (chars[*pos].is_ascii_alphanumeric() || chars[*pos] == '_') {
-                val.push(chars[*pos]);
-                *pos += 1;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(source: &str) -> String {
+        let tokens = tokenize(source).unwrap();
+        assert_eq!(tokens[0].ttype, TokenType::Str);
+        tokens[0].value.to_string()
+    }
+
+    #[test]
+    fn triple_string_strips_common_indent() {
+        assert_eq!(string("\"\"\"\n    a\n      b\n    \"\"\""), "a\n  b\n");
+    }
+
+    #[test]
+    fn unicode_whitespace_is_not_indent() {
+        assert_eq!(strip_indent(" x\n\u{a0}y"), " x\n\u{a0}y");
+        assert_eq!(strip_indent("  x\n  \u{3000}y"), "x\n\u{3000}y");
+        assert_eq!(string("\"\"\"\n x\n\u{a0}y\n\"\"\""), " x\n\u{a0}y\n");
+    }
+
+    #[test]
+    fn tabs_and_spaces_share_only_their_common_run() {
+        assert_eq!(strip_indent("\t x\n\t  y\n\tz"), " x\n  y\nz");
+    }
+
+    #[test]
+    fn triple_string_after_crlf_is_a_block() {
+        assert_eq!(string("\"\"\"\r\n    a\r\n    b\r\n    \"\"\""), "a\r\nb\r\n");
+    }
+    #[test]
+    fn adjacent_strings_are_joined() {
+        assert_eq!(string("\"a\"\n    \"b\""), "ab");
+    }
+
+    #[test]
+    fn comments_between_adjacent_strings_are_skipped() {
+        let tokens = tokenize("\"a\" # first\n# and a line of its own\n\"b\";").unwrap();
+        assert_eq!(tokens[0].value, "ab");
+        assert_eq!(tokens[1].ttype, TokenType::Scln);
+        assert_eq!(tokens[1].line_num, 3);
+    }
+
+    #[test]
+    fn raw_strings_join_adjacent_strings() {
+        assert_eq!(string("r\"\\d\" \"\\n\""), "\\d\n");
+        assert_eq!(string("\"\\n\" r\"\\d\""), "\n\\d");
+    }
+
+    #[test]
+    fn comment_after_a_string_is_not_part_of_it() {
+        let tokens = tokenize("\"a\" # \"b\"\n;").unwrap();
+        assert_eq!(tokens[0].value, "a");
+        assert_eq!(tokens[1].ttype, TokenType::Scln);
+    }
+
+    #[test]
+    fn radix_literals_that_overflow_are_too_large() {
+        assert_eq!(tokenize("0x7FFF_FFFF_FFFF_FFFF").unwrap()[0].value, i64::MAX.to_string());
+        for source in ["0xFFFFFFFFFFFFFFFFF", &format!("0b1{}", "0".repeat(63)), "0o1000000000000000000000"] {
+            match tokenize(source) {
+                Err(LexerError::IntegerTooLarge(literal, 0)) => assert_eq!(literal, source),
+                other => panic!("expected {} to be too large, got {:?}", source, other),
             }
-            let token_type = match val.as_str() {
-                "loop" => TokenType::Loop,
-                "if" => TokenType::If,
-                "elif" => TokenType::Elif,
-                "else" => TokenType::Else,
-                "true" => TokenType::True,
-                "false" => TokenType::False,
-                "break" => TokenType::Break,
-                "return" => TokenType::Return,
-                "import" => TokenType::Import,
-                "pub" => TokenType::Pub,
-                "fn" => TokenType::Func,
-                "and" => TokenType::And,
-                "or" => TokenType::Or,
-                "let" => TokenType::Let,
-                _ => TokenType::Iden,
-            };
-            return Some(Token { ttype: token_type, value: val, line_num: *line_number});
-        } else {
-            eprintln!("[!] [Lexer Error] Unknown character '{}' at line {}", chars[*pos], line_number);
-            return None;
         }
+        assert!(matches!(tokenize("0b12"), Err(LexerError::InvalidNumber(..))));
+    }
 
-        *pos += 1;
+    fn number(source: &str) -> (TokenType, String) {
+        let tokens = tokenize(source).unwrap();
+        (tokens[0].ttype.clone(), tokens[0].value.to_string())
     }
 
-    Some(Token {
-        ttype: TokenType::Eof,
-        value: String::new(),
-        line_num: *line_number})
+    #[test]
+    fn radix_and_scientific_literals() {
+        assert_eq!(number("0xff"), (TokenType::Num, "255".into()));
+        assert_eq!(number("0XFF"), (TokenType::Num, "255".into()));
+        assert_eq!(number("0b101"), (TokenType::Num, "5".into()));
+        assert_eq!(number("0o17"), (TokenType::Num, "15".into()));
+        assert_eq!(number("1.5e3"), (TokenType::Flt, "1.5e3".into()));
+        assert_eq!(number("2E-4"), (TokenType::Flt, "2E-4".into()));
+        assert!(matches!(tokenize("0x"), Err(LexerError::ExpectedDigits(prefix, 0)) if prefix == "0x"));
+        assert!(matches!(tokenize("0b102"), Err(LexerError::InvalidNumber(..))));
+        assert!(matches!(tokenize("1e+"), Err(LexerError::ExpectedExponent(..))));
+    }
+
+    #[test]
+    fn underscores_separate_digits() {
+        assert_eq!(number("1_000_000"), (TokenType::Num, "1000000".into()));
+        assert_eq!(number("0xFF_FF"), (TokenType::Num, "65535".into()));
+        assert_eq!(number("1_000.000_1"), (TokenType::Flt, "1000.0001".into()));
+        for source in ["1_", "1__", "0x_FF", "1_.5", "1._5"] {
+            assert!(matches!(tokenize(source), Err(LexerError::MisplacedUnderscore(..))), "{}", source);
+        }
+    }
+
+    #[test]
+    fn string_escapes() {
+        assert_eq!(string(r#""a\tb\r\n\0\'\"\\""#), "a\tb\r\n\0'\"\\");
+        assert_eq!(string(r#""\u{41}\u{1F600}""#), "A\u{1F600}");
+        assert!(matches!(tokenize(r#""\q""#), Err(LexerError::InvalidEscape('q', 1))));
+        assert!(matches!(tokenize(r#""\u41""#), Err(LexerError::ExpectedUnicodeBrace(1))));
+        assert!(matches!(tokenize(r#""\u{41""#), Err(LexerError::UnterminatedUnicodeEscape(..))));
+        assert!(matches!(tokenize(r#""\u{D800}""#), Err(LexerError::InvalidUnicodeEscape(..))));
+        assert!(matches!(tokenize(r#""\u{1234567}""#), Err(LexerError::InvalidUnicodeEscape(..))));
+    }
+
+    #[test]
+    fn raw_strings_keep_backslashes() {
+        assert_eq!(string(r#"r"C:\dir\n""#), r"C:\dir\n");
+        assert_eq!(string("r\"a\nb\""), "a\nb");
+        assert!(matches!(tokenize(r#"r"abc"#), Err(LexerError::UnterminatedString(0))));
+        let tokens = tokenize("r + 1").unwrap();
+        assert_eq!((tokens[0].ttype.clone(), &*tokens[0].value), (TokenType::Iden, "r"));
+    }
+
+    #[test]
+    fn unterminated_strings_point_at_their_start() {
+        for source in ["let x = \"abc", "let x = \"\"\"\nabc\"\"", "let x = r\"abc", "let x = \"abc\\"] {
+            match tokenize(source) {
+                Err(e @ LexerError::UnterminatedString(8)) => assert_eq!(e.offset(), 8),
+                other => panic!("expected {:?} to be unterminated, got {:?}", source, other),
+            }
+        }
+    }
+
+    #[test]
+    fn token_values_borrow_unless_they_differ_from_the_source() {
+        let tokens = tokenize("let name = \"plain\" + \"a\\tb\" + 1_000 + 0xff;").unwrap();
+        let borrowed: Vec<bool> = tokens.iter().map(|token| matches!(token.value, Cow::Borrowed(_))).collect();
+        assert_eq!(borrowed, vec![true, true, true, true, true, false, true, false, true, false, true, true]);
+        assert_eq!(tokens[1].offset, 4);
+        assert_eq!(tokens[1].end, 8);
+        let owned: Token<'static> = tokens[5].clone().into_owned();
+        assert_eq!(owned.value, "a\tb");
+    }
 }
@@ -0,0 +1,31 @@
+// Named lint rules the parser can check while it builds the AST. Every rule
+// is on in the CLI unless turned off with --allow=<name>.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Lint {
+    UnusedVar,
+    Shadowing,
+    UnreachableCode,
+    ConstantCondition,
+}
+
+pub const ALL: &[Lint] = &[
+    Lint::UnusedVar,
+    Lint::Shadowing,
+    Lint::UnreachableCode,
+    Lint::ConstantCondition,
+];
+
+impl Lint {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::UnusedVar => "unused-var",
+            Lint::Shadowing => "shadowing",
+            Lint::UnreachableCode => "unreachable-code",
+            Lint::ConstantCondition => "constant-condition",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Lint> {
+        ALL.iter().copied().find(|lint| lint.name() == name)
+    }
+}
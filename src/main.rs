@@ -1,74 +1,312 @@
+mod codegen;
+mod dot;
+mod driver;
 mod lexer;
+mod lint;
 mod parser;
+mod source;
+mod suggest;
 
-use std::fs;
+use std::collections::HashSet;
 use std::env;
+use std::fs;
+use std::path::Path;
 
 fn main() -> std::io::Result<()> {
 
     let args: Vec<String> = env::args().collect();
 
-    if args.len() < 2 {
-        eprintln!("Usage: {} <filename>", args[0]);
-        std::process::exit(1);
+    if args.len() >= 2 && args[1] == "build" {
+        return build(&args);
+    }
+    if args.len() >= 2 && args[1] == "lint" {
+        return lint(&args);
     }
 
-    let filename = &args[1];
-    let contents = fs::read_to_string(filename)?;
+    let filenames: Vec<&String> = args[1..].iter().filter(|arg| !arg.starts_with("--")).collect();
+    if filenames.is_empty() {
+        usage(&args[0]);
+    }
 
-    let file_buffer = fs::read_to_string(filename)?;
+    // --ast=tree (the default) prints the tree, --ast=dot a Graphviz digraph
+    let dot = match args.iter().find_map(|arg| arg.strip_prefix("--ast=")) {
+        None | Some("tree") => false,
+        Some("dot") => true,
+        Some(format) => {
+            eprintln!("[!] [Driver Error] Unknown AST format '{}' (expected: tree, dot)", format);
+            std::process::exit(1);
+        }
+    };
+
+    let mut sources = source::SourceManager::new();
+    let files: Vec<source::FileId> = filenames.iter()
+        .map(|filename| sources.add(filename, read_source_or_exit(filename)))
+        .collect();
+
+    for &file in &files {
+        let tokens = tokenize_or_exit(&sources, file);
+        if dot {
+            let program = parse_program_or_exit(&sources, file, new_parser(tokens, &args));
+            print!("{}", dot::emit(&program));
+            continue;
+        }
 
-    let mut pos = 0;
-    let mut line_number = 0;
-    let mut token = lexer::lex(&file_buffer, &mut pos, &mut line_number);
-    pos = pos + 1;
+        let mut parser = new_parser(tokens, &args);
+
+        if files.len() > 1 {
+            println!("AST ({}):", sources.name(file));
+        } else {
+            println!("AST:");
+        }
 
-    let mut pos = 0;
-    let mut tokens = Vec::new();
-    loop {
-        match lexer::lex(&file_buffer, &mut pos, &mut line_number) {
-            Some(tok) => {
-                // println!("DEBUG {:?}", tok);
-                if tok.ttype == lexer::TokenType::Eof {
-                    tokens.push(tok);
+        while !parser.is_at_end() {
+            let result = parser.parse_statement();
+            report_recorded(&sources, file, &mut parser);
+            match result {
+                Ok(ast) => {
+                    pretty_print(&ast, "", true);
+                }
+                Err(e) => {
+                    report_parse_error(&sources, file, &e);
                     break;
                 }
-                tokens.push(tok);
+            }
+        }
+    }
+
+    return Ok(());
+}
+
+fn usage(program: &str) -> ! {
+    eprintln!("Usage: {} [--ast=tree|dot] [options] <filename>...", program);
+    eprintln!("       {} build --target=js|c [options] <filename>", program);
+    eprintln!("       {} lint [options] <filename>...", program);
+    eprintln!("Options: --max-depth=N, --allow=<lint> (unused-var, shadowing, unreachable-code, constant-condition)");
+    std::process::exit(1);
+}
+
+// --max-depth=N changes how deeply expressions and blocks may nest and
+// --allow=<lint> turns one lint off, all of them are on otherwise. Missing
+// semicolons are always recovered from so one slip reports one error.
+fn new_parser<'src>(tokens: Vec<lexer::Token<'src>>, args: &[String]) -> parser::Parser<'src> {
+    let mut parser = parser::Parser::new(tokens);
+    parser.set_recover(true);
+
+    let mut lints: HashSet<lint::Lint> = lint::ALL.iter().copied().collect();
+    for name in args.iter().filter_map(|arg| arg.strip_prefix("--allow=")) {
+        match lint::Lint::from_name(name) {
+            Some(allowed) => {
+                lints.remove(&allowed);
             }
             None => {
-                // LEXICAL ERRORS
-                break;
+                eprintln!("[!] [Driver Error] Unknown lint '{}'", name);
+                std::process::exit(1);
             }
         }
     }
+    parser.set_lints(lints);
+
+    for arg in args {
+        if let Some(depth) = arg.strip_prefix("--max-depth=") {
+            match depth.parse() {
+                Ok(depth) => parser.set_max_depth(depth),
+                Err(_) => {
+                    eprintln!("[!] [Driver Error] Invalid --max-depth value '{}'", depth);
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+    parser
+}
 
-    let mut parser = parser::Parser::new(tokens);
+// tong build --target=js file.tong writes file.js next to the source,
+// --target=c (or --emit=c) writes file.c
+fn build(args: &[String]) -> std::io::Result<()> {
+    let mut target = None;
+    let mut filename = None;
+    for arg in &args[2..] {
+        match arg.strip_prefix("--target=").or_else(|| arg.strip_prefix("--emit=")) {
+            Some(t) => target = Some(t),
+            None if !arg.starts_with("--") => filename = Some(arg),
+            None => {}
+        }
+    }
+
+    let (target, filename) = match (target, filename) {
+        (Some(target), Some(filename)) => (target, filename),
+        _ => usage(&args[0]),
+    };
+
+    let emit = match target {
+        "js" => codegen::js::emit,
+        "c" => codegen::c::emit,
+        _ => {
+            eprintln!("[!] [Driver Error] Unknown build target '{}' (expected: js, c)", target);
+            std::process::exit(1);
+        }
+    };
 
-    println!("AST:");
+    let mut sources = source::SourceManager::new();
+    let file = sources.add(filename, read_source_or_exit(filename));
+    let tokens = tokenize_or_exit(&sources, file);
 
-    while !parser.is_at_end() {
-        match parser.parse_statement() {
-            Ok(ast) => {
-                pretty_print(&ast, "", true);
+    let program = parse_program_or_exit(&sources, file, new_parser(tokens, args));
+
+    let output = match emit(&program) {
+        Ok(output) => output,
+        Err(e) => {
+            eprintln!("Code generation failed: {}", e);
+            if let codegen::CodegenError::Undefined(_, Some(name)) = &e {
+                eprintln!("    did you mean '{}'?", name);
             }
+            std::process::exit(1);
+        }
+    };
+
+    fs::write(Path::new(filename).with_extension(target), output)
+}
+
+// tong lint file.tong parses each file only for its diagnostics and exits
+// non-zero if there was anything to report
+fn lint(args: &[String]) -> std::io::Result<()> {
+    let filenames: Vec<&String> = args[2..].iter().filter(|arg| !arg.starts_with("--")).collect();
+    if filenames.is_empty() {
+        usage(&args[0]);
+    }
+
+    let mut sources = source::SourceManager::new();
+    let mut clean = true;
+    for filename in filenames {
+        let file = sources.add(filename, read_source_or_exit(filename));
+        let tokens = match lexer::tokenize(sources.text(file)) {
+            Ok(tokens) => tokens,
             Err(e) => {
-                match e {
-                    parser::ParserError::UnexpectedToken(_, line)
-                    | parser::ParserError::UnterminatedBlock(line)
-                    | parser::ParserError::ExpectedSemicolon(line)
-                    | parser::ParserError::ExpectedToken(_, line) => {
-                        eprintln!("Parsing failed at line {}: {:?}", line, e);
-                    }
-                    _ => {
-                        eprintln!("Parsing failed: {:?}", e);
-                    }
-                }
+                report_lex_error(&sources, file, &e);
+                clean = false;
+                continue;
+            }
+        };
+
+        let mut parser = new_parser(tokens, args);
+        while !parser.is_at_end() {
+            let result = parser.parse_statement();
+            let (warnings, errors) = report_recorded(&sources, file, &mut parser);
+            if warnings + errors > 0 {
+                clean = false;
+            }
+            if let Err(e) = result {
+                report_parse_error(&sources, file, &e);
+                clean = false;
                 break;
             }
         }
     }
 
-    return Ok(());
+    if !clean {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+// reports every recovered error as well as the one that stopped parsing
+fn parse_program_or_exit(sources: &source::SourceManager, file: source::FileId, mut parser: parser::Parser) -> Vec<parser::ASTNode> {
+    let mut program = Vec::new();
+    let mut failed = false;
+    while !parser.is_at_end() {
+        let result = parser.parse_statement();
+        let (_, errors) = report_recorded(sources, file, &mut parser);
+        if errors > 0 {
+            failed = true;
+        }
+        match result {
+            Ok(ast) => program.push(ast),
+            Err(e) => {
+                report_parse_error(sources, file, &e);
+                std::process::exit(1);
+            }
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+    program
+}
+
+fn read_source_or_exit(filename: &str) -> String {
+    match driver::read_source(filename) {
+        Ok(source) => source,
+        Err(e) => {
+            report_driver_error(&e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn tokenize_or_exit(sources: &source::SourceManager, file: source::FileId) -> Vec<lexer::Token<'_>> {
+    match lexer::tokenize(sources.text(file)) {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            report_lex_error(sources, file, &e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// a file that cannot be read has no line or column, just its path
+fn report_driver_error(e: &driver::DriverError) {
+    eprintln!("{}: Reading failed: {}", e.path(), e);
+    if let driver::DriverError::NotFound(_, Some(name)) = e {
+        eprintln!("    did you mean '{}'?", name);
+    }
+}
+
+fn report_lex_error(sources: &source::SourceManager, file: source::FileId, e: &lexer::LexerError) {
+    eprintln!("{}: Lexing failed: {}", sources.location(file, e.offset()), e);
+}
+
+fn report_parse_error(sources: &source::SourceManager, file: source::FileId, e: &parser::ParserError) {
+    eprintln!("{}: Parsing failed: {}", sources.location(file, e.offset()), e);
+    if let parser::ParserError::AssignToConst(name, declaration, _) = e {
+        eprintln!("    note: '{}' is declared constant at {}", name, sources.location(file, *declaration));
+    }
+}
+
+// everything the parser recorded since the last call, returns how many
+// warnings and recovered errors there were
+fn report_recorded(sources: &source::SourceManager, file: source::FileId, parser: &mut parser::Parser) -> (usize, usize) {
+    let warnings = parser.take_warnings();
+    for warning in &warnings {
+        report_parse_warning(sources, file, warning);
+    }
+    let errors = parser.take_errors();
+    for e in &errors {
+        report_parse_error(sources, file, e);
+    }
+    (warnings.len(), errors.len())
+}
+
+fn report_parse_warning(sources: &source::SourceManager, file: source::FileId, warning: &parser::ParserWarning) {
+    let name = warning.lint().name();
+    match warning {
+        parser::ParserWarning::Shadowed(iden, previous, offset) => {
+            eprintln!("{}: warning: '{}' shadows an outer declaration [{}]", sources.location(file, *offset), iden, name);
+            eprintln!("    note: previous declaration at {}", sources.location(file, *previous));
+        }
+        parser::ParserWarning::Redeclared(iden, previous, offset) => {
+            eprintln!("{}: warning: '{}' is declared twice in the same block [{}]", sources.location(file, *offset), iden, name);
+            eprintln!("    note: previous declaration at {}", sources.location(file, *previous));
+        }
+        parser::ParserWarning::Unused(iden, offset) => {
+            eprintln!("{}: warning: '{}' is never read [{}]", sources.location(file, *offset), iden, name);
+        }
+        parser::ParserWarning::Unreachable(offset) => {
+            eprintln!("{}: warning: unreachable code after return or break [{}]", sources.location(file, *offset), name);
+        }
+        parser::ParserWarning::ConstantCondition(offset) => {
+            eprintln!("{}: warning: condition is a constant [{}]", sources.location(file, *offset), name);
+        }
+    }
 }
 
 use parser::ASTNode;
@@ -84,8 +322,12 @@ fn pretty_print(node: &ASTNode, prefix: &str, is_last: bool) {
             println!("End of file.");
         }
 
-        ASTNode::Number(n) => {
-            println!("Number({})", n);
+        ASTNode::Integer(n) => {
+            println!("Integer({})", n);
+        }
+
+        ASTNode::Float(n) => {
+            println!("Float({})", n);
         }
 
         ASTNode::Identifier(iden) => {
@@ -112,15 +354,31 @@ fn pretty_print(node: &ASTNode, prefix: &str, is_last: bool) {
             println!("StrLiteral(\"{}\")", s);
         }
 
-        ASTNode::BinOpNode { op, left, right } => {
+        ASTNode::UnaryOpNode { op, operand } => {
+            println!("UnaryOp('{}')", op);
+            let new_prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
+            pretty_print(operand, &new_prefix, true);
+        }
+
+        ASTNode::BinOpNode { op, left, right, .. } => {
             println!("BinOp('{}')", op);
             let new_prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
             pretty_print(left, &new_prefix, false);
             pretty_print(right, &new_prefix, true);
         }
 
-        ASTNode::VarDecNode { name, value } => {
-            println!("VarDec({})", name);
+        ASTNode::ChainedCompareNode { operands, ops } => {
+            let ops: Vec<String> = ops.iter().map(|(op, _)| format!("'{}'", op)).collect();
+            println!("ChainedCompare({})", ops.join(", "));
+            let new_prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
+            for (i, operand) in operands.iter().enumerate() {
+                pretty_print(operand, &new_prefix, i == operands.len() - 1);
+            }
+        }
+
+        ASTNode::VarDecNode { name, value, constant, public } => {
+            let kind = if *constant { "ConstDec" } else { "VarDec" };
+            println!("{}({}{})", kind, if *public { "pub " } else { "" }, name);
             let new_prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
             pretty_print(value, &new_prefix, true);
         }
@@ -173,6 +431,35 @@ fn pretty_print(node: &ASTNode, prefix: &str, is_last: bool) {
             }
         }
 
+        ASTNode::DoWhileNode { block, condition } => {
+            println!("DoWhile");
+            let new_prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
+            for stmt in block {
+                pretty_print(stmt, &new_prefix, false);
+            }
+            pretty_print(condition, &new_prefix, true);
+        }
+
+        ASTNode::ParamNode { name, default, variadic } => {
+            println!("Param({}{})", if *variadic { "..." } else { "" }, name);
+            if let Some(default) = default {
+                let new_prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
+                pretty_print(default, &new_prefix, true);
+            }
+        }
+
+        ASTNode::SpreadNode(expr) => {
+            println!("Spread");
+            let new_prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
+            pretty_print(expr, &new_prefix, true);
+        }
+
+        ASTNode::NamedArgNode { name, value } => {
+            println!("NamedArg({})", name);
+            let new_prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
+            pretty_print(value, &new_prefix, true);
+        }
+
         ASTNode::FuncCall { name, arguments } => {
             println!("FuncCall({})", name);
             let new_prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
@@ -181,19 +468,82 @@ fn pretty_print(node: &ASTNode, prefix: &str, is_last: bool) {
             }
         }
 
-        ASTNode::FuncDef { name, arguments, block } => {
-            println!("FuncDef({})", name);
+        ASTNode::FuncDef { name, arguments, block, public } => {
+            println!("FuncDef({}{})", if *public { "pub " } else { "" }, name);
             let new_prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
 
-            println!("{}├── Args: {:?}", new_prefix, arguments);
+            println!("{}├── Args", new_prefix);
+            for (i, arg) in arguments.iter().enumerate() {
+                pretty_print(arg, &format!("{}│   ", new_prefix), i == arguments.len() - 1);
+            }
 
             for (i, stmt) in block.iter().enumerate() {
                 pretty_print(stmt, &new_prefix, i == block.len() - 1);
             }
         }
 
-        ASTNode::ImportNode ( name ) => {
-            println!("Import({})", name);
+        ASTNode::ImportNode { path, alias, names } => {
+            println!("Import({})", path);
+            let new_prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
+            if let Some(alias) = alias {
+                println!("{}└── As({})", new_prefix, alias);
+            }
+            for (i, name) in names.iter().enumerate() {
+                println!("{}{}Name({})", new_prefix, if i == names.len() - 1 { "└── " } else { "├── " }, name);
+            }
+        }
+
+        ASTNode::ClassDef { name, parent, fields, methods } => {
+            match parent {
+                Some(parent) => println!("Class({} : {})", name, parent),
+                None => println!("Class({})", name),
+            }
+            let new_prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
+            let members: Vec<&ASTNode> = fields.iter().chain(methods.iter()).collect();
+            for (i, member) in members.iter().enumerate() {
+                pretty_print(member, &new_prefix, i == members.len() - 1);
+            }
+        }
+
+        ASTNode::MethodCall { object, method, arguments, optional } => {
+            println!("MethodCall({}{})", if *optional { "?." } else { "." }, method);
+            let new_prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
+            pretty_print(object, &new_prefix, arguments.is_empty());
+            for (i, arg) in arguments.iter().enumerate() {
+                pretty_print(arg, &new_prefix, i == arguments.len() - 1);
+            }
+        }
+
+        ASTNode::FieldAssignNode { object, field, value } => {
+            println!("AssignField(.{})", field);
+            let new_prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
+            pretty_print(object, &new_prefix, false);
+            pretty_print(value, &new_prefix, true);
+        }
+
+        ASTNode::MemberNode { object, field, optional } => {
+            println!("Member({}{})", if *optional { "?." } else { "." }, field);
+            let new_prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
+            pretty_print(object, &new_prefix, true);
+        }
+
+        ASTNode::TupleNode(items) => {
+            println!("Tuple");
+            let new_prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
+            for (i, item) in items.iter().enumerate() {
+                pretty_print(item, &new_prefix, i == items.len() - 1);
+            }
+        }
+
+        ASTNode::DestructureNode { pattern, value, constant, declare } => {
+            let kind = match (declare, constant) {
+                (true, true) => "ConstDec",
+                (true, false) => "VarDec",
+                (false, _) => "Assign",
+            };
+            println!("{}({})", kind, pattern);
+            let new_prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
+            pretty_print(value, &new_prefix, true);
         }
     }
 }
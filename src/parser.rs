@@ -1,23 +1,98 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
 use crate::lexer::{Token, TokenType};
+use crate::lint::Lint;
+use crate::suggest;
 
+// every error carries the byte offset it points at, the driver turns it
+// into file:line:col through source::SourceManager
 #[derive(Debug )]
 pub enum ParserError {
-    UnexpectedToken(String, u32),
-    UnterminatedBlock(u32),
-    ExpectedSemicolon(u32),
-    ExpectedToken(String, u32),
+    UnexpectedToken(String, usize),
+    UnterminatedBlock(usize),
+    ExpectedSemicolon(usize),
+    ExpectedToken(String, usize),
+    TooDeep(usize),
+    // an identifier where a statement keyword was likely meant: (found, keyword)
+    MisspelledKeyword(String, &'static str, usize),
+    // (name, offset of the const declaration, offset of the assignment)
+    AssignToConst(String, usize, usize),
+}
+
+impl ParserError {
+    pub fn offset(&self) -> usize {
+        match self {
+            ParserError::UnexpectedToken(_, offset)
+            | ParserError::UnterminatedBlock(offset)
+            | ParserError::ExpectedSemicolon(offset)
+            | ParserError::ExpectedToken(_, offset)
+            | ParserError::TooDeep(offset)
+            | ParserError::MisspelledKeyword(_, _, offset)
+            | ParserError::AssignToConst(_, _, offset) => *offset,
+        }
+    }
+}
+
+impl fmt::Display for ParserError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParserError::UnexpectedToken(token, _) => write!(f, "unexpected token: {}", token),
+            ParserError::UnterminatedBlock(_) => write!(f, "unterminated block"),
+            ParserError::ExpectedSemicolon(_) => write!(f, "expected ';'"),
+            ParserError::ExpectedToken(token, _) => write!(f, "expected {}", token),
+            ParserError::TooDeep(_) => write!(f, "nesting is too deep"),
+            ParserError::MisspelledKeyword(found, keyword, _) => {
+                write!(f, "unexpected identifier '{}'\n    did you mean '{}'?", found, keyword)
+            }
+            ParserError::AssignToConst(name, _, _) => write!(f, "cannot assign to constant '{}'", name),
+        }
+    }
 }
 
+// Problems that do not stop parsing, each reported by one lint::Lint
 #[derive(Debug)]
+pub enum ParserWarning {
+    // (name, offset of the earlier declaration, offset of the new one)
+    Shadowed(String, usize, usize),
+    Redeclared(String, usize, usize),
+    // (name, offset of the declaration)
+    Unused(String, usize),
+    // offset of the first statement after a return or break
+    Unreachable(usize),
+    // offset of the literal condition
+    ConstantCondition(usize),
+}
+
+impl ParserWarning {
+    pub fn lint(&self) -> Lint {
+        match self {
+            ParserWarning::Shadowed(..) | ParserWarning::Redeclared(..) => Lint::Shadowing,
+            ParserWarning::Unused(..) => Lint::UnusedVar,
+            ParserWarning::Unreachable(_) => Lint::UnreachableCode,
+            ParserWarning::ConstantCondition(_) => Lint::ConstantCondition,
+        }
+    }
+}
+
+// no Eq or Hash: Float holds an f64
+#[derive(Clone, Debug, PartialEq)]
 pub enum ASTNode {
 
     Eof,
 
-    Number(f64),
+    Integer(i64),
+
+    Float(f64),
 
     Identifier(String),
 
-    ImportNode(String),
+    // import "path", import "path" as alias, import "path" { a, b }
+    ImportNode {
+        path: String,
+        alias: Option<String>,
+        names: Vec<String>,
+    },
 
     StrLiteral(String),
 
@@ -27,15 +102,32 @@ pub enum ASTNode {
 
     ReturnNode(Option<Box<ASTNode>>),
 
+    UnaryOpNode {
+        op: UnaryOp,
+        operand: Box<ASTNode>,
+    },
+
+    // line is where the operator is, for the backends' runtime errors
     BinOpNode {
-        op: String,
+        op: BinaryOp,
         left: Box<ASTNode>,
         right: Box<ASTNode>,
+        line: u32,
+    },
+
+    // 0 <= x < 10: ops[i] compares operands[i] with operands[i + 1]. Each
+    // operand is evaluated once, and none after a comparison is false.
+    ChainedCompareNode {
+        operands: Vec<ASTNode>,
+        ops: Vec<(BinaryOp, u32)>,
     },
 
     VarDecNode {
         name: String,
         value: Box<ASTNode>,
+        constant: bool,
+        // declared with pub, visible to modules importing this one
+        public: bool,
     },
 
     AssignNode {
@@ -55,6 +147,25 @@ pub enum ASTNode {
         block: Vec<ASTNode>,
     },
 
+    // do { ... } while (condition); runs the block before the first check
+    DoWhileNode {
+        block: Vec<ASTNode>,
+        condition: Box<ASTNode>,
+    },
+
+    ParamNode {
+        name: String,
+        default: Option<Box<ASTNode>>,
+        variadic: bool,
+    },
+
+    SpreadNode(Box<ASTNode>),
+
+    NamedArgNode {
+        name: String,
+        value: Box<ASTNode>,
+    },
+
     FuncCall {
         name: String,
         arguments: Vec<ASTNode>,
@@ -64,22 +175,387 @@ pub enum ASTNode {
         name: String,
         arguments: Vec<ASTNode>,
         block: Vec<ASTNode>,
+        public: bool,
+    },
+
+    // class Name { let field = value; fn method() { ... } }, or
+    // class Name : Parent { ... } to inherit from Parent. fields holds
+    // VarDecNodes and methods FuncDefs; init is the constructor and self
+    // is the instance inside methods.
+    ClassDef {
+        name: String,
+        parent: Option<String>,
+        fields: Vec<ASTNode>,
+        methods: Vec<ASTNode>,
+    },
+
+    // object.method(arguments), or object?.method(arguments) which is nil
+    // without calling anything when object is nil
+    MethodCall {
+        object: Box<ASTNode>,
+        method: String,
+        arguments: Vec<ASTNode>,
+        optional: bool,
+    },
+
+    // object.field = value
+    FieldAssignNode {
+        object: Box<ASTNode>,
+        field: String,
+        value: Box<ASTNode>,
+    },
+
+    // (a, b), a single element tuple is written (a,)
+    TupleNode(Vec<ASTNode>),
+
+    // object.field, or object?.field which is nil when object is nil
+    MemberNode {
+        object: Box<ASTNode>,
+        field: String,
+        optional: bool,
+    },
+
+    // let (x, y) = value and const (x, y) = value declare the names,
+    // (x, y) = value assigns to them
+    DestructureNode {
+        pattern: Pattern,
+        value: Box<ASTNode>,
+        constant: bool,
+        declare: bool,
     },
 
 }
 
-pub struct Parser {
-    tokens: Vec<Token>,
+// the left hand side of a destructuring let, const or assignment
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pattern {
+    // (x, y)
+    Tuple(Vec<String>),
+    // [first, second], the leading elements of an array
+    Array(Vec<String>),
+    // { name, age }, each name bound to the key of the same name
+    Map(Vec<String>),
+}
+
+impl Pattern {
+    pub fn names(&self) -> &[String] {
+        match self {
+            Pattern::Tuple(names) | Pattern::Array(names) | Pattern::Map(names) => names,
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pattern::Tuple(names) if names.len() == 1 => write!(f, "({},)", names[0]),
+            Pattern::Tuple(names) => write!(f, "({})", names.join(", ")),
+            Pattern::Array(names) => write!(f, "[{}]", names.join(", ")),
+            Pattern::Map(names) => write!(f, "{{ {} }}", names.join(", ")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BinaryOp {
+    And,
+    Or,
+    Eq,
+    Ge,
+    Le,
+    Gt,
+    Lt,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    Coalesce,
+    In,
+}
+
+impl BinaryOp {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOp::And => "and",
+            BinaryOp::Or => "or",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ge => ">=",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Lt => "<",
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Mod => "%",
+            BinaryOp::Pow => "**",
+            BinaryOp::Coalesce => "??",
+            BinaryOp::In => "in",
+        }
+    }
+
+    // the operators that chain, a < b <= c. == does not, so a < b == c
+    // still compares the result of a < b.
+    pub fn is_ordering(&self) -> bool {
+        matches!(self, BinaryOp::Ge | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Lt)
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum UnaryOp {
+    Neg,
+}
+
+impl UnaryOp {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            UnaryOp::Neg => "-",
+        }
+    }
+}
+
+impl fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Assoc {
+    Left,
+    Right,
+}
+
+// Binary operators, the node they build and their precedence, higher binds
+// tighter. New operators only need a row here and a BinaryOp variant.
+const BINARY_OPERATORS: &[(TokenType, BinaryOp, u8, Assoc)] = &[
+    (TokenType::And, BinaryOp::And, 1, Assoc::Left),
+    (TokenType::Or,  BinaryOp::Or,  1, Assoc::Left),
+
+    (TokenType::Eqv, BinaryOp::Eq,  2, Assoc::Left),
+    (TokenType::Geq, BinaryOp::Ge,  2, Assoc::Left),
+    (TokenType::Leq, BinaryOp::Le,  2, Assoc::Left),
+    (TokenType::Gre, BinaryOp::Gt,  2, Assoc::Left),
+    (TokenType::Les, BinaryOp::Lt,  2, Assoc::Left),
+    (TokenType::In,  BinaryOp::In,  2, Assoc::Left),
+
+    // x ?? 0 > 5 compares the defaulted value
+    (TokenType::Coalesce, BinaryOp::Coalesce, 3, Assoc::Right),
+
+    (TokenType::Add, BinaryOp::Add, 4, Assoc::Left),
+    (TokenType::Sub, BinaryOp::Sub, 4, Assoc::Left),
+
+    (TokenType::Mul, BinaryOp::Mul, 5, Assoc::Left),
+    (TokenType::Div, BinaryOp::Div, 5, Assoc::Left),
+    (TokenType::Mod, BinaryOp::Mod, 5, Assoc::Left),
+
+    (TokenType::Pow, BinaryOp::Pow, 6, Assoc::Right),
+];
+
+// unary minus binds tighter than * but looser than **, so -2 ** 2 is -(2 ** 2)
+const UNARY_PRECEDENCE: u8 = 6;
+
+fn binary_operator(ttype: &TokenType) -> Option<(BinaryOp, u8, Assoc)> {
+    BINARY_OPERATORS.iter()
+        .find(|(token, _, _, _)| token == ttype)
+        .map(|(_, op, prec, assoc)| (*op, *prec, *assoc))
+}
+
+// keywords that start a statement, suggested when an identifier in their
+// place fails to parse
+const STATEMENT_KEYWORDS: &[&str] = &["let", "const", "fn", "class", "if", "elif", "else", "loop", "do", "return", "break", "import"];
+
+fn misspelled_keyword(name: &str) -> Option<&'static str> {
+    suggest::closest(name, STATEMENT_KEYWORDS.iter().copied())
+}
+
+// how deeply expressions and blocks may nest before parsing gives up with
+// TooDeep instead of overflowing the native stack
+pub const DEFAULT_MAX_DEPTH: usize = 256;
+
+pub struct Parser<'src> {
+    tokens: Vec<Token<'src>>,
     pos: usize,
+    depth: usize,
+    max_depth: usize,
+    recover: bool,
+    errors: Vec<ParserError>,
+    // names declared by let, const or as parameters in each enclosing block
+    scopes: Vec<HashMap<String, Declaration>>,
+    lints: HashSet<Lint>,
+    warnings: Vec<ParserWarning>,
+}
+
+#[derive(Clone, Copy)]
+struct Declaration {
+    offset: usize,
+    constant: bool,
+    used: bool,
 }
 
-impl Parser {
+impl<'src> Parser<'src> {
 
-    pub fn new(tokens: Vec<Token>) -> Self {
+    pub fn new(tokens: Vec<Token<'src>>) -> Self {
         return Self {
             tokens,
             pos: 0,
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+            recover: false,
+            errors: Vec::new(),
+            scopes: vec![HashMap::new()],
+            lints: HashSet::new(),
+            warnings: Vec::new(),
+        };
+    }
+
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    // with recovery on, errors the parser can carry on past (a missing `;`,
+    // assigning to a constant) are recorded instead of returned
+    pub fn set_recover(&mut self, recover: bool) {
+        self.recover = recover;
+    }
+
+    // errors recorded during recovery since the last call
+    pub fn take_errors(&mut self) -> Vec<ParserError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    // lints are all off until enabled here
+    pub fn set_lints(&mut self, lints: HashSet<Lint>) {
+        self.lints = lints;
+    }
+
+    fn warn(&mut self, warning: ParserWarning) {
+        if self.lints.contains(&warning.lint()) {
+            self.warnings.push(warning);
+        }
+    }
+
+    pub fn take_warnings(&mut self) -> Vec<ParserWarning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    // runs f one nesting level deeper
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T, ParserError>) -> Result<T, ParserError> {
+        if self.depth >= self.max_depth {
+            return Err(ParserError::TooDeep(self.current()?.offset));
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        return result;
+    }
+
+    // a missing `;` is reported just past the token before it, not at the
+    // start of whatever follows (often on the next line)
+    fn expect_semicolon(&mut self) -> Result<(), ParserError> {
+        if self.current()?.ttype == TokenType::Scln {
+            self.consume();
+            return Ok(());
+        }
+
+        return self.recoverable(ParserError::ExpectedSemicolon(self.previous_end()));
+    }
+
+    fn recoverable(&mut self, error: ParserError) -> Result<(), ParserError> {
+        if self.recover {
+            self.errors.push(error);
+            return Ok(());
+        }
+        return Err(error);
+    }
+
+    fn declare(&mut self, name: &str, offset: usize, constant: bool) {
+        let warning = match self.scopes.split_last() {
+            Some((innermost, outer)) => {
+                if let Some(previous) = innermost.get(name) {
+                    Some(ParserWarning::Redeclared(name.to_string(), previous.offset, offset))
+                } else {
+                    outer.iter().rev().find_map(|scope| scope.get(name))
+                        .map(|previous| ParserWarning::Shadowed(name.to_string(), previous.offset, offset))
+                }
+            }
+            None => None,
+        };
+        if let Some(warning) = warning {
+            self.warn(warning);
+        }
+
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), Declaration { offset, constant, used: false });
+        }
+    }
+
+    fn mark_used(&mut self, name: &str) {
+        if let Some(declaration) = self.scopes.iter_mut().rev().find_map(|scope| scope.get_mut(name)) {
+            declaration.used = true;
+        }
+    }
+
+    // leaving a block or function: anything declared there and never read
+    // is unused. Top level declarations are not checked, another file may
+    // import them.
+    fn pop_scope(&mut self) {
+        let scope = match self.scopes.pop() {
+            Some(scope) => scope,
+            None => return,
+        };
+        let mut unused: Vec<(String, usize)> = scope.into_iter()
+            .filter(|(_, declaration)| !declaration.used)
+            .map(|(name, declaration)| (name, declaration.offset))
+            .collect();
+        unused.sort_by_key(|(_, offset)| *offset);
+        for (name, offset) in unused {
+            self.warn(ParserWarning::Unused(name, offset));
+        }
+    }
+
+    // a literal condition always takes (or, for a loop, never enters) the
+    // same branch
+    fn check_condition(&mut self, condition: &ASTNode, offset: usize, is_loop: bool) {
+        let truthy = match condition {
+            ASTNode::BoolNode(b) => *b,
+            ASTNode::Integer(n) => *n != 0,
+            ASTNode::Float(n) => *n != 0.0,
+            ASTNode::StrLiteral(s) => !s.is_empty(),
+            _ => return,
         };
+        // loop (true) is how an endless loop is written
+        if is_loop && truthy {
+            return;
+        }
+        self.warn(ParserWarning::ConstantCondition(offset));
+    }
+
+    // where name was declared constant, if the innermost binding is a const
+    fn constant_declaration(&self, name: &str) -> Option<usize> {
+        match self.scopes.iter().rev().find_map(|scope| scope.get(name)) {
+            Some(declaration) if declaration.constant => Some(declaration.offset),
+            _ => None,
+        }
+    }
+
+    fn previous_end(&self) -> usize {
+        match self.pos.checked_sub(1).and_then(|pos| self.tokens.get(pos)) {
+            Some(token) => token.end,
+            None => 0,
+        }
+    }
+
+    fn end_offset(&self) -> usize {
+        self.tokens.last().map_or(0, |token| token.offset)
     }
 
     fn consume(&mut self) {
@@ -94,9 +570,9 @@ impl Parser {
         }
     }
 
-    fn current(&self) -> Result<&Token, ParserError> {
+    fn current(&self) -> Result<&Token<'src>, ParserError> {
         self.tokens.get(self.pos)
-            .ok_or(ParserError::UnexpectedToken("Unexpected end of input".into(), 0))
+            .ok_or(ParserError::UnexpectedToken("Unexpected end of input".into(), self.end_offset()))
     }    
 
     fn parse_factor(&mut self) -> Result<ASTNode, ParserError> {
@@ -104,9 +580,15 @@ impl Parser {
 
         match token.ttype {
             TokenType::Num => {
+                let num = token.value.parse::<i64>().unwrap_or_default();
+                self.consume();
+                return Ok(ASTNode::Integer(num));
+            }
+
+            TokenType::Flt => {
                 let num = token.value.parse::<f64>().unwrap_or_default();
                 self.consume();
-                return Ok(ASTNode::Number(num));
+                return Ok(ASTNode::Float(num));
             }
 
             TokenType::Iden => { 
@@ -117,14 +599,15 @@ impl Parser {
                 }
                 else { 
                     self.puke(); 
-                    let iden = self.current()?.value.clone();
+                    let iden = self.current()?.value.to_string();
                     self.consume();
+                    self.mark_used(&iden);
                     return Ok(ASTNode::Identifier(iden));
                 }
             }
 
             TokenType::Str => {
-                let iden = token.value.clone();
+                let iden = token.value.to_string();
                 self.consume();
                 return Ok(ASTNode::StrLiteral(iden));
             }
@@ -139,13 +622,25 @@ impl Parser {
                 return Ok(ASTNode::BoolNode(false));
             }
 
+            TokenType::Sub => {
+                self.consume();
+                let operand = self.parse_binary_expr(UNARY_PRECEDENCE)?;
+                return Ok(ASTNode::UnaryOpNode {
+                    op: UnaryOp::Neg,
+                    operand: Box::new(operand),
+                });
+            }
+
             TokenType::Opt => { 
                 self.consume();
                 let node = self.parse_expr(false)?; 
+                if self.current()?.ttype == TokenType::Com {
+                    return self.parse_tuple(node);
+                }
                 let next = self.current()?; 
                 if next.ttype != TokenType::Cpt {
                     // self.shout_err("Expected closing parenthesis after expression", Some(&next));
-                    return Err(ParserError::ExpectedToken("closing parenthesis".into(), next.line_num));
+                    return Err(ParserError::ExpectedToken("closing parenthesis".into(), next.offset));
                 }
                 self.consume();
                 return Ok(node);
@@ -153,195 +648,213 @@ impl Parser {
 
             _ => {
                     // self.shout_err("Unexpected token in factor", self.current());
-                    return Err(ParserError::UnexpectedToken(token.value.clone(), token.line_num));
+                    return Err(ParserError::UnexpectedToken(token.value.to_string(), token.offset));
                 }
         }
     }
 
-    fn parse_term(&mut self) -> Result<ASTNode, ParserError> {
-        let mut node = self.parse_factor()?;
+    // a factor followed by any number of .field or ?.field accesses and
+    // .method(...) calls
+    fn parse_member(&mut self) -> Result<ASTNode, ParserError> {
+        let node = self.parse_factor()?;
+        self.parse_member_chain(node)
+    }
 
+    fn parse_member_chain(&mut self, mut node: ASTNode) -> Result<ASTNode, ParserError> {
         loop {
-            let token = match self.current() {
-                Ok(token) => token,
-                Err(_) => break,
-            };
-            match token.ttype {
-                TokenType::Mul | TokenType::Div | TokenType::Mod => {
-                    let op = token.value.clone();
-                    self.consume();
-                    node = ASTNode::BinOpNode {
-                        op,
-                        left: Box::new(node),
-                        right: Box::new(self.parse_factor()?),
-                    };
-                }
+            let optional = match self.current() {
+                Ok(token) if token.ttype == TokenType::Dot => false,
+                Ok(token) if token.ttype == TokenType::OptChain => true,
                 _ => break,
-            }
-        }
-
-        return Ok(node);
-    }
+            };
+            self.consume(); // consume . or ?.
 
-    fn parse_arith_expr(&mut self) -> Result<ASTNode, ParserError> {
-        let mut node = self.parse_term()?;
+            let token = self.current()?;
+            if token.ttype != TokenType::Iden {
+                return Err(ParserError::ExpectedToken("field name".into(), token.offset));
+            }
+            let field = token.value.to_string();
+            self.consume();
+
+            if self.current()?.ttype == TokenType::Opt {
+                let arguments = self.parse_args_call()?;
+                node = ASTNode::MethodCall {
+                    object: Box::new(node),
+                    method: field,
+                    arguments,
+                    optional,
+                };
+                continue;
+            }
 
-        loop {
-            let token = match self.current() {
-                Ok(token) => token,
-                Err(_) => break,
+            node = ASTNode::MemberNode {
+                object: Box::new(node),
+                field,
+                optional,
             };
-            match token.ttype {
-                TokenType::Add | TokenType::Sub => {
-                    let op = token.value.clone();
-                    self.consume();
-                    node = ASTNode::BinOpNode {
-                        op,
-                        left: Box::new(node),
-                        right: Box::new(self.parse_term()?),
-                    };
-                }
-                _ => break,
-            }
         }
 
         return Ok(node);
     }
 
-    fn parse_comp_expr(&mut self) -> Result<ASTNode, ParserError> {
-        let mut node = self.parse_arith_expr()?;
+    // the rest of a tuple literal after its first element, up to and
+    // including the closing parenthesis
+    fn parse_tuple(&mut self, first: ASTNode) -> Result<ASTNode, ParserError> {
+        let mut items = vec![first];
 
         loop {
-            let token = match self.current() {
-                Ok(token) => token,
-                Err(_) => break,
-            };
-            match token.ttype {
-                TokenType::Geq | TokenType::Leq | TokenType::Gre | TokenType::Les | TokenType::Eqv => {
-                    let op = token.value.clone();
-                    self.consume();
-                    node = ASTNode::BinOpNode {
-                        op,
-                        left: Box::new(node),
-                        right: Box::new(self.parse_arith_expr()?),
-                    };
-                }
-                _ => break,
+            self.consume(); // consume ,
+            // a comma before ) is a trailing comma: (a, b,)
+            if self.current()?.ttype == TokenType::Cpt {
+                break;
+            }
+            items.push(self.parse_expr(false)?);
+
+            match self.current()?.ttype {
+                TokenType::Com => {}
+                TokenType::Cpt => break,
+                _ => return Err(ParserError::ExpectedToken("',' or ')'".into(), self.current()?.offset)),
             }
         }
+        self.consume(); // consume )
 
-        return Ok(node);
+        return Ok(ASTNode::TupleNode(items));
     }
 
-    fn parse_logic_expr(&mut self) -> Result<ASTNode, ParserError> {
-        let mut node = self.parse_comp_expr()?;
+    // every nested expression (parentheses, operands, call arguments) comes
+    // through here, so this is where expression depth is counted
+    fn parse_binary_expr(&mut self, min_prec: u8) -> Result<ASTNode, ParserError> {
+        self.nested(|parser| parser.parse_binary_operators(min_prec))
+    }
 
-        loop {
-            let token = match self.current() {
-                Ok(token) => token,
-                Err(_) => break,
+    // precedence climbing over BINARY_OPERATORS: only operators binding at
+    // least as tight as min_prec are folded into this subexpression
+    fn parse_binary_operators(&mut self, min_prec: u8) -> Result<ASTNode, ParserError> {
+        let mut node = self.parse_member()?;
+        // whether node is an ordering comparison another one chains onto
+        let mut chaining = false;
+
+        while let Ok(token) = self.current() {
+            let (op, prec, assoc) = match binary_operator(&token.ttype) {
+                Some((op, prec, assoc)) if prec >= min_prec => (op, prec, assoc),
+                _ => break,
             };
-            match token.ttype {
-                TokenType::And | TokenType::Or => {
-                    let op = token.value.clone();
-                    self.consume();
-                    node = ASTNode::BinOpNode {
-                        op,
-                        left: Box::new(node),
-                        right: Box::new(self.parse_comp_expr()?),
-                    };
+            let line = token.line_num;
+            self.consume();
+
+            let next_prec = match assoc {
+                Assoc::Left => prec + 1,
+                Assoc::Right => prec,
+            };
+            let right = self.parse_binary_expr(next_prec)?;
+
+            node = match node {
+                ASTNode::BinOpNode { op: first, left, right: middle, line: first_line } if chaining && op.is_ordering() => {
+                    ASTNode::ChainedCompareNode {
+                        operands: vec![*left, *middle, right],
+                        ops: vec![(first, first_line), (op, line)],
+                    }
                 }
-                _ => break,
-            }
+                ASTNode::ChainedCompareNode { mut operands, mut ops } if chaining && op.is_ordering() => {
+                    operands.push(right);
+                    ops.push((op, line));
+                    ASTNode::ChainedCompareNode { operands, ops }
+                }
+                node => ASTNode::BinOpNode {
+                    op,
+                    left: Box::new(node),
+                    right: Box::new(right),
+                    line,
+                },
+            };
+            chaining = op.is_ordering();
         }
 
         return Ok(node);
     }
 
+    // parse_factor reports tokens that cannot start an expression
     fn parse_expr(&mut self, terminate: bool) -> Result<ASTNode, ParserError> {
-        match self.current()?.ttype {
-            
-            TokenType::Iden | TokenType::Num | TokenType::Str | 
-            TokenType::True | TokenType::False => {
-                let mut node = self.parse_logic_expr()?;
-                if terminate == true && self.current()?.ttype != TokenType::Scln {
-                    // self.shout_err("Expected Semicolon", self.current());
-                    return Err(ParserError::ExpectedSemicolon(self.current()?.line_num));
-                } else if terminate == true && self.current()?.ttype == TokenType::Scln {
-                    self.consume();
-                }
-
-                return Ok(node);
-            }
-
-            _ =>    {
-                        Err(ParserError::UnexpectedToken("Invalid start of expression".into(), self.current()?.line_num))
-                    },
+        let node = self.parse_binary_expr(0)?;
+        if terminate {
+            self.expect_semicolon()?;
         }
 
+        return Ok(node);
     }
 
     pub fn parse_statement(&mut self) -> Result<ASTNode, ParserError> {
         match self.current()?.ttype {
             TokenType::Eof => return Ok(ASTNode::Eof),
             TokenType::Import => self.parse_import(),
-            TokenType::Let => self.parse_var_def(),
+            TokenType::Let => self.parse_var_def(false),
+            TokenType::Const => self.parse_var_def(true),
             TokenType::Func => self.parse_func_def(),
+            TokenType::Class => self.parse_class_def(),
+            TokenType::Pub => self.parse_pub(),
             TokenType::If => self.parse_ifelse(),
             TokenType::Loop => self.parse_loop(),
+            TokenType::Do => self.parse_do_while(),
+            TokenType::Opt if self.destructure_ahead() => self.parse_destructure(false, false),
 
             TokenType::Break => {
                                     self.consume(); // consume break
-                                    
-                                    if self.current()?.ttype != TokenType::Scln {
-                                        // self.shout_err("Expected Semicolon", self.current());
-                                        return Err(ParserError::ExpectedSemicolon(self.current()?.line_num));
-                                    }
-
-                                    self.consume();
+                                    self.expect_semicolon()?;
                                     Ok(ASTNode::BreakNode)
                                 }
 
             TokenType::Return => {
                 self.consume(); // consume return
-                if let Ok(node) = self.parse_expr(true) {
-
-                    Ok(ASTNode::ReturnNode(Some(Box::new(node))))
-
-                } else {
-
-                    if self.current()?.ttype != TokenType::Scln {
-                        // self.shout_err("Expected Semicolon", self.current());
-                        return Err(ParserError::ExpectedSemicolon(self.current()?.line_num));
-                    }
-
-                    self.consume();
+                if self.current()?.ttype == TokenType::Scln {
+                    self.expect_semicolon()?;
                     Ok(ASTNode::ReturnNode(None))
+                } else {
+                    let node = self.parse_expr(true)?;
+                    Ok(ASTNode::ReturnNode(Some(Box::new(node))))
                 }
             }
 
             TokenType::Iden => {
 
+                let name = self.current()?.value.to_string();
+                let offset = self.current()?.offset;
+
                 self.consume();
                 if self.current()?.ttype == TokenType::Opt {
                     self.puke();
-                    let node = self.parse_func_call();
+                    let node = self.parse_func_call()?;
 
-                    if self.current()?.ttype != TokenType::Scln {
-                        // self.shout_err("Expected Semicolon", self.current());
-                        return Err(ParserError::ExpectedSemicolon(self.current()?.line_num));
+                    // `iff (x) { ... }` parses as a call followed by a block
+                    if self.current()?.ttype == TokenType::Ocl {
+                        if let Some(keyword) = misspelled_keyword(&name) {
+                            return Err(ParserError::MisspelledKeyword(name, keyword, offset));
+                        }
                     }
 
-                    self.consume();
-                    return node;
+                    // Dog("rex").speak();
+                    if matches!(self.current()?.ttype, TokenType::Dot | TokenType::OptChain) {
+                        let node = self.parse_member_chain(node)?;
+                        return self.finish_member_statement(node, offset);
+                    }
+
+                    self.expect_semicolon()?;
+                    return Ok(node);
                 }
                 else if self.current()?.ttype == TokenType::Equ {
                     self.puke();
                     return self.parse_assign();
                 }
+                else if matches!(self.current()?.ttype, TokenType::Dot | TokenType::OptChain) {
+                    self.puke();
+                    return self.parse_member_statement();
+                }
+                else if let Some(keyword) = misspelled_keyword(&name) {
+                    // `fnn main() { ... }`, `lett x = 1;`
+                    return Err(ParserError::MisspelledKeyword(name, keyword, offset));
+                }
                 else {  
                     // self.shout_err("Unexpected token in statement", self.current());
-                    return Err(ParserError::UnexpectedToken(self.current()?.value.clone(), self.current()?.line_num)); 
+                    return Err(ParserError::UnexpectedToken(self.current()?.value.to_string(), self.current()?.offset)); 
                 }
             }
 
@@ -354,56 +867,110 @@ impl Parser {
 
         if self.current()?.ttype != TokenType::Str {
             // self.shout_err("Invalid Module", self.current());
-            return Err(ParserError::UnexpectedToken("Invalid module string".into(), self.current()?.line_num));
+            return Err(ParserError::UnexpectedToken("Invalid module string".into(), self.current()?.offset));
         }
 
-        let name = self.current()?.value.clone();
+        let path = self.current()?.value.to_string();
         self.consume();
 
-        return Ok(ASTNode::ImportNode(name));
+        let mut alias = None;
+        let mut names = Vec::new();
+
+        if self.current()?.ttype == TokenType::Iden && self.current()?.value == "as" {
+            self.consume(); // consume as
+            let token = self.current()?;
+            if token.ttype != TokenType::Iden {
+                return Err(ParserError::ExpectedToken("module alias".into(), token.offset));
+            }
+            let (name, offset) = (token.value.to_string(), token.offset);
+            self.consume();
+            self.declare(&name, offset, false);
+            alias = Some(name);
+        } else if self.current()?.ttype == TokenType::Ocl {
+            self.consume(); // consume {
+            loop {
+                let token = self.current()?;
+                if token.ttype == TokenType::Ccl {
+                    break;
+                }
+                if token.ttype != TokenType::Iden {
+                    return Err(ParserError::ExpectedToken("imported name".into(), token.offset));
+                }
+                let (name, offset) = (token.value.to_string(), token.offset);
+                self.consume();
+                if names.contains(&name) {
+                    return Err(ParserError::UnexpectedToken(format!("'{}' is imported twice", name), offset));
+                }
+                self.declare(&name, offset, false);
+                names.push(name);
+
+                match self.current()?.ttype {
+                    TokenType::Com => self.consume(), // consume , (a trailing one is fine)
+                    TokenType::Ccl => {}
+                    _ => return Err(ParserError::ExpectedToken("',' or '}'".into(), self.current()?.offset)),
+                }
+            }
+            self.consume(); // consume }
+            if names.is_empty() {
+                return Err(ParserError::ExpectedToken("imported name".into(), self.previous_end()));
+            }
+        }
+
+        // the terminating ; is optional for imports
+        if self.current()?.ttype == TokenType::Scln {
+            self.consume();
+        }
+
+        return Ok(ASTNode::ImportNode { path, alias, names });
     }
 
     fn parse_block(&mut self) -> Result<Vec<ASTNode>, ParserError> {
+        self.nested(|parser| parser.parse_block_statements())
+    }
+
+    fn parse_block_statements(&mut self) -> Result<Vec<ASTNode>, ParserError> {
 
         if self.current()?.ttype != TokenType::Ocl {
             // self.shout_err("Expected opening brace '{' for block", self.current());
-            return Err(ParserError::ExpectedToken("{".into(), self.current()?.line_num));
+            return Err(ParserError::ExpectedToken("{".into(), self.current()?.offset));
         }
 
         self.consume(); // consume {
         
         let mut statements: Vec<ASTNode> = Vec::new();
-        
-        // seems very inefficient but rust whines if I try to 
-        // use a reference here so I have to clone the token
-        // and then use it in the loop condition 
-        
-        let thetype = self.current()?.ttype.clone();
-        let thenum = self.current()?.line_num;
-        let theval = self.current()?.value.clone();
 
+        self.scopes.push(HashMap::new());
+        let mut unreachable_reported = false;
         loop {
-
-            if thetype == TokenType::Ccl {
+            let ttype = &self.current()?.ttype;
+            if *ttype == TokenType::Ccl || *ttype == TokenType::Eof {
                 break;
             }
 
+            let diverged = matches!(statements.last(), Some(ASTNode::ReturnNode(_) | ASTNode::BreakNode));
+            if diverged && !unreachable_reported {
+                self.warn(ParserWarning::Unreachable(self.current()?.offset));
+                unreachable_reported = true;
+            }
+
             match self.parse_statement() {
-                Ok(node) => statements.push(node),
-                Err(_) => {
-                    return Err(ParserError::UnexpectedToken(theval, thenum));
+                Ok(statement) => statements.push(statement),
+                Err(e) => {
+                    self.scopes.pop();
+                    return Err(e);
                 }
             }
         } 
+        self.pop_scope();
 
         if self.current()?.ttype != TokenType::Ccl {
             // self.shout_err("Unterminated block", self.current());
-            return Err(ParserError::UnterminatedBlock(self.current()?.line_num)); // unterminated block
+            return Err(ParserError::UnterminatedBlock(self.current()?.offset)); // unterminated block
         }
 
         if self.current()?.ttype != TokenType::Ccl {
             // self.shout_err("Expected closing brace '}' for block", self.current());
-            return Err(ParserError::ExpectedToken("}".into(), self.current()?.line_num));
+            return Err(ParserError::ExpectedToken("}".into(), self.current()?.offset));
         }
 
         self.consume(); // Consume }
@@ -411,45 +978,313 @@ impl Parser {
         return Ok(statements);
     }
 
-    fn parse_var_def(&mut self) -> Result<ASTNode, ParserError> {
-        self.consume(); // consume the 'let'
+    fn parse_var_def(&mut self, constant: bool) -> Result<ASTNode, ParserError> {
+        self.consume(); // consume the 'let' or 'const'
+
+        if matches!(self.current()?.ttype, TokenType::Opt | TokenType::Osq | TokenType::Ocl) {
+            return self.parse_destructure(true, constant);
+        }
 
-        let name = self.current()?.value.clone();
+        let name = self.current()?.value.to_string();
+        let offset = self.current()?.offset;
         self.consume();
         self.consume(); // consume the '='
         let value = self.parse_expr(true)?;
 
+        self.declare(&name, offset, constant);
+
         let mut node = ASTNode::VarDecNode {
             name: name,
             value: Box::new(value),
+            constant,
+            public: false,
+        };
+
+        return Ok(node);
+    }
+
+    // `(x, y) = ...` at the start of a statement is a destructuring
+    // assignment, anything else in parentheses is an expression
+    fn destructure_ahead(&self) -> bool {
+        let mut tokens = self.tokens[self.pos..].iter().skip(1); // skip (
+        loop {
+            match tokens.next().map(|token| &token.ttype) {
+                Some(TokenType::Iden) | Some(TokenType::Com) => continue,
+                Some(TokenType::Cpt) => break,
+                _ => return false,
+            }
+        }
+        matches!(tokens.next().map(|token| &token.ttype), Some(TokenType::Equ))
+    }
+
+    // (x, y) = value, after the let or const if there is one. Declarations
+    // may also unpack an array, [a, b] = value, or a map, { a, b } = value.
+    fn parse_destructure(&mut self, declare: bool, constant: bool) -> Result<ASTNode, ParserError> {
+        let offset = self.current()?.offset;
+        let open = self.current()?.ttype.clone();
+        let (close, closing) = match open {
+            TokenType::Osq => (TokenType::Csq, "']'"),
+            TokenType::Ocl => (TokenType::Ccl, "'}'"),
+            _ => (TokenType::Cpt, "')'"),
+        };
+        self.consume(); // consume the opening bracket
+
+        let mut names: Vec<(String, usize)> = Vec::new();
+        let mut comma = false;
+        loop {
+            let token = self.current()?;
+            if token.ttype == close {
+                break;
+            }
+            if token.ttype != TokenType::Iden {
+                return Err(ParserError::ExpectedToken("name to bind".into(), token.offset));
+            }
+            let (name, name_offset) = (token.value.to_string(), token.offset);
+            self.consume();
+            if names.iter().any(|(other, _)| *other == name) {
+                return Err(ParserError::UnexpectedToken(format!("'{}' is bound twice", name), name_offset));
+            }
+            names.push((name, name_offset));
+
+            let ttype = &self.current()?.ttype;
+            if *ttype == TokenType::Com {
+                comma = true;
+                self.consume(); // consume , (a trailing one is fine)
+            } else if *ttype != close {
+                return Err(ParserError::ExpectedToken(format!("',' or {}", closing), self.current()?.offset));
+            }
+        }
+        self.consume(); // consume the closing bracket
+        if names.is_empty() {
+            return Err(ParserError::ExpectedToken("name to bind".into(), self.previous_end()));
+        }
+        // (x) is just x in parentheses, a one name tuple is written (x,)
+        if open == TokenType::Opt && !comma {
+            return Err(ParserError::UnexpectedToken(
+                format!("a tuple pattern needs a comma, write ({},)", names[0].0), offset));
+        }
+
+        if self.current()?.ttype != TokenType::Equ {
+            return Err(ParserError::ExpectedToken("=".into(), self.current()?.offset));
+        }
+        self.consume(); // consume =
+        let value = self.parse_expr(true)?;
+
+        // only a literal's length is known before the program runs
+        if let (TokenType::Opt, ASTNode::TupleNode(items)) = (&open, &value) {
+            if items.len() != names.len() {
+                return Err(ParserError::UnexpectedToken(
+                    format!("cannot unpack {} values into {} names", items.len(), names.len()), offset));
+            }
+        }
+
+        for (name, name_offset) in &names {
+            if declare {
+                self.declare(name, *name_offset, constant);
+            } else if let Some(declaration) = self.constant_declaration(name) {
+                self.recoverable(ParserError::AssignToConst(name.clone(), declaration, *name_offset))?;
+            }
+        }
+
+        let names: Vec<String> = names.into_iter().map(|(name, _)| name).collect();
+        let pattern = match open {
+            TokenType::Osq => Pattern::Array(names),
+            TokenType::Ocl => Pattern::Map(names),
+            _ => Pattern::Tuple(names),
         };
 
+        let node = ASTNode::DestructureNode {
+            pattern,
+            value: Box::new(value),
+            constant,
+            declare,
+        };
         return Ok(node);
     }
 
     fn parse_func_def(&mut self) -> Result<ASTNode, ParserError> { 
         self.consume(); // consume the 'fn'
 
-        let name = self.current()?.value.clone();
+        let name = self.current()?.value.to_string();
         self.consume();
-        let arguments = self.parse_args_def()?.unwrap_or_default();
+        // parameters are declared in a scope around the body, so they
+        // shadow outer names inside it
+        self.scopes.push(HashMap::new());
+        let result = self.parse_args_def()
+            .and_then(|arguments| Ok((arguments.unwrap_or_default(), self.parse_block()?)));
+        if result.is_ok() {
+            self.pop_scope();
+        } else {
+            self.scopes.pop();
+        }
+        let (arguments, block) = result?;
 
-        if let Ok(block) = self.parse_block() {  
-            let node = ASTNode::FuncDef {
-                name,
-                arguments,
-                block,
+        let node = ASTNode::FuncDef {
+            name,
+            arguments,
+            block,
+            public: false,
+        };
+        return Ok(node);
+    }
+
+    fn parse_class_def(&mut self) -> Result<ASTNode, ParserError> {
+        self.consume(); // consume class
+
+        let token = self.current()?;
+        if token.ttype != TokenType::Iden {
+            return Err(ParserError::ExpectedToken("class name".into(), token.offset));
+        }
+        let name = token.value.to_string();
+        self.consume();
+
+        let mut parent = None;
+        if self.current()?.ttype == TokenType::Colon {
+            self.consume(); // consume :
+            let token = self.current()?;
+            if token.ttype != TokenType::Iden {
+                return Err(ParserError::ExpectedToken("parent class name".into(), token.offset));
+            }
+            parent = Some(token.value.to_string());
+            self.consume();
+        }
+
+        if self.current()?.ttype != TokenType::Ocl {
+            return Err(ParserError::ExpectedToken("{".into(), self.current()?.offset));
+        }
+        self.consume(); // consume {
+
+        let (fields, methods) = self.nested(|parser| parser.parse_class_members())?;
+
+        if self.current()?.ttype != TokenType::Ccl {
+            return Err(ParserError::UnterminatedBlock(self.current()?.offset));
+        }
+        self.consume(); // consume }
+
+        let node = ASTNode::ClassDef {
+            name,
+            parent,
+            fields,
+            methods,
+        };
+        return Ok(node);
+    }
+
+    // let fields and fn methods up to the closing }. Fields are not
+    // variables, so unlike let statements they are not declared in a scope.
+    fn parse_class_members(&mut self) -> Result<(Vec<ASTNode>, Vec<ASTNode>), ParserError> {
+        let mut fields = Vec::new();
+        let mut methods = Vec::new();
+        let mut names: Vec<String> = Vec::new();
+
+        loop {
+            let offset = self.current()?.offset;
+            let (name, member) = match self.current()?.ttype {
+                TokenType::Ccl | TokenType::Eof => break,
+                TokenType::Let => {
+                    self.consume(); // consume let
+                    let token = self.current()?;
+                    if token.ttype != TokenType::Iden {
+                        return Err(ParserError::ExpectedToken("field name".into(), token.offset));
+                    }
+                    let name = token.value.to_string();
+                    self.consume();
+                    if self.current()?.ttype != TokenType::Equ {
+                        return Err(ParserError::ExpectedToken("=".into(), self.current()?.offset));
+                    }
+                    self.consume(); // consume =
+                    let value = self.parse_expr(true)?;
+                    let field = ASTNode::VarDecNode {
+                        name: name.clone(),
+                        value: Box::new(value),
+                        constant: false,
+                        public: false,
+                    };
+                    (name, field)
+                }
+                TokenType::Func => {
+                    let method = self.parse_func_def()?;
+                    let name = match &method {
+                        ASTNode::FuncDef { name, .. } => name.clone(),
+                        _ => String::new(),
+                    };
+                    (name, method)
+                }
+                _ => return Err(ParserError::ExpectedToken("'let' field or 'fn' method in class body".into(), offset)),
             };
+
+            if names.contains(&name) {
+                return Err(ParserError::UnexpectedToken(format!("'{}' is defined twice in this class", name), offset));
+            }
+            names.push(name);
+
+            match member {
+                ASTNode::FuncDef { .. } => methods.push(member),
+                _ => fields.push(member),
+            }
+        }
+
+        return Ok((fields, methods));
+    }
+
+    // a statement starting with object.field: an assignment to the field or
+    // an expression such as a method call
+    fn parse_member_statement(&mut self) -> Result<ASTNode, ParserError> {
+        let offset = self.current()?.offset;
+        let node = self.parse_expr(false)?;
+        self.finish_member_statement(node, offset)
+    }
+
+    // a parsed member expression either stands alone or is assigned to
+    fn finish_member_statement(&mut self, node: ASTNode, offset: usize) -> Result<ASTNode, ParserError> {
+        if self.current()?.ttype != TokenType::Equ {
+            self.expect_semicolon()?;
             return Ok(node);
-        } else {
-            return Err(ParserError::UnexpectedToken("Error parsing function definition".into(), self.current()?.line_num));
         }
+
+        let (object, field) = match node {
+            ASTNode::MemberNode { object, field, optional: false } => (object, field),
+            _ => return Err(ParserError::UnexpectedToken("cannot assign to this expression".into(), offset)),
+        };
+        self.consume(); // consume =
+        let value = self.parse_expr(true)?;
+
+        let node = ASTNode::FieldAssignNode {
+            object,
+            field,
+            value: Box::new(value),
+        };
+        return Ok(node);
+    }
+
+    // pub exports a top level fn, let or const to modules importing this one
+    fn parse_pub(&mut self) -> Result<ASTNode, ParserError> {
+        if self.scopes.len() > 1 {
+            return Err(ParserError::UnexpectedToken("'pub' is only allowed at the top level".into(), self.current()?.offset));
+        }
+        let offset = self.current()?.offset;
+        self.consume(); // consume pub
+
+        let mut node = match self.current()?.ttype {
+            TokenType::Let => self.parse_var_def(false)?,
+            TokenType::Const => self.parse_var_def(true)?,
+            TokenType::Func => self.parse_func_def()?,
+            _ => return Err(ParserError::ExpectedToken("fn, let or const after 'pub'".into(), self.current()?.offset)),
+        };
+
+        match &mut node {
+            ASTNode::VarDecNode { public, .. } | ASTNode::FuncDef { public, .. } => *public = true,
+            _ => return Err(ParserError::UnexpectedToken("a destructuring declaration cannot be 'pub'".into(), offset)),
+        }
+        return Ok(node);
     }
 
     fn parse_func_call(&mut self) -> Result<ASTNode, ParserError> {
-        let name = self.current()?.value.clone();
+        let name = self.current()?.value.to_string();
         self.consume();
 
+        // calling a parameter or variable reads it
+        self.mark_used(&name);
         let arguments = self.parse_args_call()?;
 
         let node = ASTNode::FuncCall {
@@ -476,13 +1311,57 @@ impl Parser {
                 Err(_) => break,
             };
 
+            // a comma before ) is a trailing comma: fn f(a, b,)
             if token.ttype == TokenType::Cpt {
                 self.consume(); // consume ) 
                 return Ok(Some(arguments));
             }
 
-            arguments.push(ASTNode::Identifier(token.value.clone()));
+            // ...name collects the remaining call arguments and must come last
+            let variadic = token.ttype == TokenType::Spread;
+            if variadic {
+                self.consume(); // consume ...
+            }
+
+            let token = self.current()?;
+            if token.ttype != TokenType::Iden {
+                return Err(ParserError::ExpectedToken("parameter name".into(), token.offset));
+            }
+
+            let name = token.value.to_string();
+            let offset = token.offset;
             self.consume(); // consume identifier 
+            self.declare(&name, offset, false);
+
+            if variadic {
+                if self.current()?.ttype == TokenType::Com {
+                    self.consume(); // consume trailing ,
+                }
+                if self.current()?.ttype != TokenType::Cpt {
+                    return Err(ParserError::UnexpectedToken(
+                        format!("Variadic parameter '{}' must be the last parameter", name), self.current()?.offset));
+                }
+                self.consume(); // consume )
+                arguments.push(ASTNode::ParamNode { name, default: None, variadic });
+                return Ok(Some(arguments));
+            }
+
+            // name = expr gives the parameter a default, and every parameter
+            // after it needs one too
+            let default = if self.current()?.ttype == TokenType::Equ {
+                self.consume(); // consume =
+                Some(Box::new(self.parse_expr(false)?))
+            } else {
+                let after_default = arguments.iter()
+                    .any(|arg| matches!(arg, ASTNode::ParamNode { default: Some(_), .. }));
+                if after_default {
+                    return Err(ParserError::UnexpectedToken(
+                        format!("Parameter '{}' needs a default value", name), self.current()?.offset));
+                }
+                None
+            };
+
+            arguments.push(ASTNode::ParamNode { name, default, variadic });
 
             // After consuming, get the next token for the separator check
             let sep_token = match self.current() {
@@ -499,11 +1378,11 @@ impl Parser {
                     return Ok(Some(arguments));
                 }
                 _ => {
-                    return Err(ParserError::UnexpectedToken("Error parsing function arguments".into(), sep_token.line_num));
+                    return Err(ParserError::UnexpectedToken("Error parsing function arguments".into(), sep_token.offset));
                 }
             }
         }
-        Err(ParserError::UnexpectedToken("Error parsing function arguments".into(), 0))
+        Err(ParserError::UnexpectedToken("Error parsing function arguments".into(), self.end_offset()))
     }
 
     fn parse_args_call(&mut self) -> Result<Vec<ASTNode>, ParserError> {
@@ -512,13 +1391,47 @@ impl Parser {
         let mut arguments = Vec::new();
 
         loop {
+            // a comma before ) is a trailing comma: foo(a, b,)
             if self.current()?.ttype == TokenType::Cpt {
                 self.consume(); // consume )
                 return Ok(arguments);
             }
 
-            let node = self.parse_expr(false)?; 
-            arguments.push(node);
+            // name = expr passes the argument by parameter name
+            let mut named = None;
+            if self.current()?.ttype == TokenType::Iden {
+                let name = self.current()?.value.to_string();
+                self.consume();
+                if self.current()?.ttype == TokenType::Equ {
+                    self.consume(); // consume =
+                    named = Some(name);
+                } else {
+                    self.puke();
+                }
+            }
+
+            let offset = self.current()?.offset;
+            let has_named = arguments.iter().any(|arg| matches!(arg, ASTNode::NamedArgNode { .. }));
+
+            if let Some(name) = named {
+                let duplicate = arguments.iter()
+                    .any(|arg| matches!(arg, ASTNode::NamedArgNode { name: other, .. } if *other == name));
+                if duplicate {
+                    return Err(ParserError::UnexpectedToken(format!("Duplicate named argument '{}'", name), offset));
+                }
+                let value = self.parse_expr(false)?;
+                arguments.push(ASTNode::NamedArgNode { name, value: Box::new(value) });
+            } else if has_named {
+                return Err(ParserError::UnexpectedToken("Positional argument after named argument".into(), offset));
+            } else if self.current()?.ttype == TokenType::Spread {
+                // ...xs spreads an array over the remaining parameters
+                self.consume(); // consume ...
+                let node = self.parse_expr(false)?;
+                arguments.push(ASTNode::SpreadNode(Box::new(node)));
+            } else {
+                let node = self.parse_expr(false)?;
+                arguments.push(node);
+            }
 
             match self.current()?.ttype {
                 TokenType::Com => {
@@ -530,7 +1443,7 @@ impl Parser {
                 }
                 _ => {
                         // self.shout_err("Error parsing at Token: (Call error)", self.current());
-                        return Err(ParserError::UnexpectedToken("Error parsing function call arguments".into(), self.current()?.line_num));
+                        return Err(ParserError::UnexpectedToken("Error parsing function call arguments".into(), self.current()?.offset));
                      }
             }
         }
@@ -540,17 +1453,28 @@ impl Parser {
     fn parse_loop(&mut self) -> Result<ASTNode, ParserError> {
         self.consume(); // consume loop identifier
 
+        // loop { ... } runs until a break, the same as loop (true) { ... }
+        if self.current()?.ttype == TokenType::Ocl {
+            let block = self.parse_block()?;
+            return Ok(ASTNode::LoopNode {
+                condition: Box::new(ASTNode::BoolNode(true)),
+                block,
+            });
+        }
+
         if self.current()?.ttype != TokenType::Opt {
             // self.shout_err("Expected opening parenthesis after 'loop'", self.current());
-            return Err(ParserError::ExpectedToken("(".into(), self.current()?.line_num));
+            return Err(ParserError::ExpectedToken("( or {".into(), self.current()?.offset));
         }
         self.consume(); // consume (
 
+        let offset = self.current()?.offset;
         let condition = self.parse_expr(false)?;
+        self.check_condition(&condition, offset, true);
 
         if self.current()?.ttype != TokenType::Cpt {
             // self.shout_err("Expected closing parenthesis after loop condition", self.current());
-            return Err(ParserError::ExpectedToken(")".into(), self.current()?.line_num));
+            return Err(ParserError::ExpectedToken(")".into(), self.current()?.offset));
         }
         self.consume(); // consume )
 
@@ -565,6 +1489,39 @@ impl Parser {
 
     }
 
+    fn parse_do_while(&mut self) -> Result<ASTNode, ParserError> {
+        self.consume(); // consume do
+
+        let block = self.parse_block()?;
+
+        if self.current()?.ttype != TokenType::While {
+            return Err(ParserError::ExpectedToken("'while' after the do block".into(), self.current()?.offset));
+        }
+        self.consume(); // consume while
+
+        if self.current()?.ttype != TokenType::Opt {
+            return Err(ParserError::ExpectedToken("(".into(), self.current()?.offset));
+        }
+        self.consume(); // consume (
+
+        let offset = self.current()?.offset;
+        let condition = self.parse_expr(false)?;
+        self.check_condition(&condition, offset, true);
+
+        if self.current()?.ttype != TokenType::Cpt {
+            return Err(ParserError::ExpectedToken(")".into(), self.current()?.offset));
+        }
+        self.consume(); // consume )
+        self.expect_semicolon()?;
+
+        let node = ASTNode::DoWhileNode {
+            block,
+            condition: Box::new(condition),
+        };
+
+        return Ok(node);
+    }
+
     /* For reference:
         
         IfElseNode {
@@ -580,31 +1537,47 @@ impl Parser {
         self.consume(); // consume if identifier
 
         if self.current()?.ttype != TokenType::Opt {
-            return Err(ParserError::ExpectedToken("(".into(), self.current()?.line_num));
+            return Err(ParserError::ExpectedToken("(".into(), self.current()?.offset));
         }
         self.consume(); // consume (
 
+        let offset = self.current()?.offset;
         let ifcondition = self.parse_expr(false)?;
+        self.check_condition(&ifcondition, offset, false);
         if self.current()?.ttype != TokenType::Cpt {
-            return Err(ParserError::ExpectedToken(")".into(), self.current()?.line_num));
+            return Err(ParserError::ExpectedToken(")".into(), self.current()?.offset));
         }
         self.consume(); // consume )
 
         let then_branch = self.parse_block()?;
 
         let mut elif_branches: Vec<(Box<ASTNode>, Vec<ASTNode>)> = Vec::new();
-        while self.current()?.ttype == TokenType::Elif {
+        loop {
 
-            self.consume(); // consume elif identifier
+            if self.current()?.ttype == TokenType::Elif {
+                self.consume(); // consume elif identifier
+            } else if self.current()?.ttype == TokenType::Else {
+                // `else if` is another spelling of elif
+                self.consume(); // consume else
+                if self.current()?.ttype != TokenType::If {
+                    self.puke();
+                    break;
+                }
+                self.consume(); // consume if
+            } else {
+                break;
+            }
 
             if self.current()?.ttype != TokenType::Opt {
-                return Err(ParserError::ExpectedToken("(".into(), self.current()?.line_num));
+                return Err(ParserError::ExpectedToken("(".into(), self.current()?.offset));
             }
             self.consume(); // consume (
 
+            let offset = self.current()?.offset;
             let elifcondition = self.parse_expr(false)?;
+            self.check_condition(&elifcondition, offset, false);
             if self.current()?.ttype != TokenType::Cpt {
-                return Err(ParserError::ExpectedToken(")".into(), self.current()?.line_num));
+                return Err(ParserError::ExpectedToken(")".into(), self.current()?.offset));
             }
             self.consume(); // consume )
 
@@ -633,12 +1606,17 @@ impl Parser {
     }
 
     fn parse_assign(&mut self) -> Result<ASTNode, ParserError> {
-        let name = self.current()?.value.clone();
+        let name = self.current()?.value.to_string();
+        let offset = self.current()?.offset;
         self.consume();
         self.consume(); // consume =
 
         let value = self.parse_expr(true)?;
 
+        if let Some(declaration) = self.constant_declaration(&name) {
+            self.recoverable(ParserError::AssignToConst(name.clone(), declaration, offset))?;
+        }
+
         let node = ASTNode::AssignNode {
             name,
             value: Box::new(value),
@@ -654,4 +1632,432 @@ impl Parser {
         }
     }
 
-}
\ No newline at end of file
+}
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::lint;
+
+    // also used by the backends' tests
+    pub(crate) fn parse(source: &str) -> Result<Vec<ASTNode>, ParserError> {
+        let mut parser = Parser::new(tokenize(source).unwrap());
+        let mut program = Vec::new();
+        while !parser.is_at_end() {
+            program.push(parser.parse_statement()?);
+        }
+        Ok(program)
+    }
+
+    // test threads have small stacks, so nesting is checked against a
+    // lower limit than DEFAULT_MAX_DEPTH
+    const TEST_MAX_DEPTH: usize = 32;
+
+    fn parse_nested(source: &str) -> Result<Vec<ASTNode>, ParserError> {
+        let mut parser = Parser::new(tokenize(source).unwrap());
+        parser.set_max_depth(TEST_MAX_DEPTH);
+        let mut program = Vec::new();
+        while !parser.is_at_end() {
+            program.push(parser.parse_statement()?);
+        }
+        Ok(program)
+    }
+
+    #[test]
+    fn deep_parentheses_are_too_deep() {
+        let within = format!("let x = {}1{};", "(".repeat(TEST_MAX_DEPTH - 1), ")".repeat(TEST_MAX_DEPTH - 1));
+        assert!(parse_nested(&within).is_ok());
+        let source = format!("let x = {}1{};", "(".repeat(TEST_MAX_DEPTH), ")".repeat(TEST_MAX_DEPTH));
+        assert!(matches!(parse_nested(&source), Err(ParserError::TooDeep(_))));
+    }
+
+    #[test]
+    fn deep_blocks_are_too_deep() {
+        let source = format!("{}{}", "if (x) {".repeat(TEST_MAX_DEPTH + 1), "}".repeat(TEST_MAX_DEPTH + 1));
+        assert!(matches!(parse_nested(&source), Err(ParserError::TooDeep(_))));
+    }
+
+    #[test]
+    fn returned_expression_errors_are_not_swallowed() {
+        let source = format!("fn f() {{ return {}1{}; }}", "(".repeat(TEST_MAX_DEPTH), ")".repeat(TEST_MAX_DEPTH));
+        assert!(matches!(parse_nested(&source), Err(ParserError::TooDeep(_))));
+        assert!(parse("fn f() { return; }").is_ok());
+    }
+
+    #[test]
+    fn flat_chains_are_not_nesting() {
+        let links = DEFAULT_MAX_DEPTH * 4;
+        assert!(parse(&format!("let x = a{};", ".b".repeat(links))).is_ok());
+        assert!(parse(&format!("let x = \"x\"{};", " + \"x\"".repeat(links))).is_ok());
+        assert!(parse(&format!("let x = f(){};", ".g()".repeat(links))).is_ok());
+    }
+
+    #[test]
+    fn method_called_on_a_call_result_is_a_statement() {
+        let program = parse("Dog(\"rex\").speak();").unwrap();
+        match &program[..] {
+            [ASTNode::MethodCall { object, method, .. }] => {
+                assert_eq!(method, "speak");
+                assert!(matches!(**object, ASTNode::FuncCall { .. }));
+            }
+            other => panic!("expected a method call, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn calling_a_parameter_uses_it() {
+        assert!(warnings("fn apply(f) { return f(1); }").is_empty());
+    }
+
+    #[test]
+    fn any_factor_starts_an_expression() {
+        assert!(parse("let x = (1 + 2) * 3;\nlet y = -x;\nlet z = \"a\" in \"ab\";\nreturn (x);").is_ok());
+        match parse("let x = ;") {
+            Err(ParserError::UnexpectedToken(token, _)) => assert_eq!(token, ";"),
+            other => panic!("expected an unexpected token, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn chained_comparisons_keep_each_operand_once() {
+        let program = parse("let x = 0 <= f() < 10 > y;").unwrap();
+        match &program[..] {
+            [ASTNode::VarDecNode { value, .. }] => match &**value {
+                ASTNode::ChainedCompareNode { operands, ops } => {
+                    assert_eq!(operands.len(), 4);
+                    assert!(matches!(operands[1], ASTNode::FuncCall { .. }));
+                    assert_eq!(ops, &vec![(BinaryOp::Le, 1), (BinaryOp::Lt, 1), (BinaryOp::Gt, 1)]);
+                }
+                other => panic!("expected a chained comparison, got {:?}", other),
+            },
+            other => panic!("expected a declaration, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parenthesized_comparisons_do_not_chain() {
+        let program = parse("let x = (a < b) < c;\nlet y = a < b == c;").unwrap();
+        for node in &program {
+            match node {
+                ASTNode::VarDecNode { value, .. } => assert!(matches!(**value, ASTNode::BinOpNode { .. })),
+                other => panic!("expected a declaration, got {:?}", other),
+            }
+        }
+    }
+
+    // the value of `let x = <source>;`
+    fn value(source: &str) -> ASTNode {
+        match parse(&format!("let x = {};", source)).unwrap().pop() {
+            Some(ASTNode::VarDecNode { value, .. }) => *value,
+            other => panic!("expected a declaration, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn integers_and_floats_are_separate_literals() {
+        assert_eq!(value("42"), ASTNode::Integer(42));
+        assert_eq!(value("9223372036854775807"), ASTNode::Integer(i64::MAX));
+        assert_eq!(value("1.5"), ASTNode::Float(1.5));
+        assert_eq!(value("2e3"), ASTNode::Float(2000.0));
+        assert!(matches!(tokenize("9223372036854775808"), Err(crate::lexer::LexerError::IntegerTooLarge(..))));
+    }
+
+    fn binary(op: BinaryOp, left: ASTNode, right: ASTNode) -> ASTNode {
+        ASTNode::BinOpNode { op, left: Box::new(left), right: Box::new(right), line: 1 }
+    }
+
+    #[test]
+    fn power_is_right_associative_and_binds_tighter_than_multiplication() {
+        let (two, three) = (ASTNode::Integer(2), ASTNode::Integer(3));
+        assert_eq!(value("2 ** 3 ** 2"), binary(BinaryOp::Pow, two.clone(), binary(BinaryOp::Pow, three.clone(), two.clone())));
+        assert_eq!(value("3 * 2 ** 2"), binary(BinaryOp::Mul, three, binary(BinaryOp::Pow, two.clone(), two)));
+    }
+
+    fn name(name: &str) -> ASTNode {
+        ASTNode::Identifier(name.to_string())
+    }
+
+    #[test]
+    fn binary_operators_follow_precedence_and_associativity() {
+        assert_eq!(value("a + b * c"), binary(BinaryOp::Add, name("a"), binary(BinaryOp::Mul, name("b"), name("c"))));
+        assert_eq!(value("a - b - c"), binary(BinaryOp::Sub, binary(BinaryOp::Sub, name("a"), name("b")), name("c")));
+        assert_eq!(value("a + b == c % d"), binary(BinaryOp::Eq,
+            binary(BinaryOp::Add, name("a"), name("b")),
+            binary(BinaryOp::Mod, name("c"), name("d"))));
+        assert_eq!(value("a == b and c"), binary(BinaryOp::And, binary(BinaryOp::Eq, name("a"), name("b")), name("c")));
+    }
+
+    fn arguments(source: &str) -> Vec<ASTNode> {
+        match parse(source).unwrap().pop() {
+            Some(ASTNode::FuncCall { arguments, .. }) | Some(ASTNode::FuncDef { arguments, .. }) => arguments,
+            other => panic!("expected a call or a definition, got {:?}", other),
+        }
+    }
+
+    fn param(name: &str, default: Option<ASTNode>, variadic: bool) -> ASTNode {
+        ASTNode::ParamNode { name: name.to_string(), default: default.map(Box::new), variadic }
+    }
+
+    #[test]
+    fn trailing_commas_in_calls_and_parameters() {
+        assert_eq!(arguments("f(1, 2,);"), vec![ASTNode::Integer(1), ASTNode::Integer(2)]);
+        assert_eq!(arguments("fn f(a, b,) { }"), vec![param("a", None, false), param("b", None, false)]);
+        assert!(parse("f(,);").is_err());
+        assert!(parse("f(1,,);").is_err());
+    }
+
+    #[test]
+    fn parameters_take_default_values() {
+        assert_eq!(arguments("fn f(a, b = 2, c = a) { }"), vec![
+            param("a", None, false),
+            param("b", Some(ASTNode::Integer(2)), false),
+            param("c", Some(name("a")), false),
+        ]);
+        match parse("fn f(a = 1, b) { }") {
+            Err(ParserError::UnexpectedToken(message, _)) => assert_eq!(message, "Parameter 'b' needs a default value"),
+            other => panic!("expected a missing default, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn variadic_parameters_and_spread_arguments() {
+        assert_eq!(arguments("fn sum(first, ...rest) { }"), vec![param("first", None, false), param("rest", None, true)]);
+        assert_eq!(arguments("sum(...xs, 1);"), vec![ASTNode::SpreadNode(Box::new(name("xs"))), ASTNode::Integer(1)]);
+        assert!(parse("fn f(...a, b) { }").is_err());
+    }
+
+    #[test]
+    fn named_arguments_follow_positional_ones() {
+        let named = ASTNode::NamedArgNode { name: "sep".to_string(), value: Box::new(ASTNode::StrLiteral(",".to_string())) };
+        assert_eq!(arguments("join(xs, sep = \",\");"), vec![name("xs"), named]);
+        assert!(parse("f(a = 1, 2);").is_err());
+        assert!(parse("f(b = 1, b = 2);").is_err());
+    }
+
+    #[test]
+    fn operator_symbols_lex_back_to_their_tokens() {
+        for (token, op, _, _) in BINARY_OPERATORS {
+            assert_eq!(&tokenize(op.symbol()).unwrap()[0].ttype, token, "{}", op);
+        }
+        assert_eq!(value("-x"), ASTNode::UnaryOpNode { op: UnaryOp::Neg, operand: Box::new(name("x")) });
+        assert_eq!(UnaryOp::Neg.to_string(), "-");
+    }
+
+    #[test]
+    fn tokens_and_trees_compare_and_hash() {
+        let source = "fn f(a, b = 1.5) { return a ?? b; }";
+        assert_eq!(parse(source).unwrap(), parse(source).unwrap());
+        assert_ne!(parse("let x = 1;").unwrap(), parse("let x = 2;").unwrap());
+        let tokens = tokenize("a + a").unwrap();
+        let distinct: HashSet<&Token> = tokens.iter().collect();
+        assert_eq!(distinct.len(), 4);
+        let kinds: HashSet<TokenType> = tokens.into_iter().map(|token| token.ttype).collect();
+        assert_eq!(kinds.len(), 3);
+    }
+
+    #[test]
+    fn else_if_is_elif() {
+        assert_eq!(
+            parse("if (a) { f(); } else if (b) { g(); } else { h(); }").unwrap(),
+            parse("if (a) { f(); } elif (b) { g(); } else { h(); }").unwrap(),
+        );
+        match parse("if (a) { } else if (b) { } else if (c) { }").unwrap().pop() {
+            Some(ASTNode::IfElseNode { elif_branch, else_branch, .. }) => {
+                assert_eq!(elif_branch.len(), 2);
+                assert!(else_branch.is_none());
+            }
+            other => panic!("expected an if, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn misspelled_keywords_are_suggested() {
+        for (source, keyword) in [("fnn main() { }", "fn"), ("lett x = 1;", "let"), ("iff (x) { }", "if"), ("retrun x;", "return")] {
+            match parse(source) {
+                Err(ParserError::MisspelledKeyword(_, suggested, 0)) => assert_eq!(suggested, keyword),
+                other => panic!("expected '{}' for {:?}, got {:?}", keyword, source, other),
+            }
+        }
+        assert!(parse("iff(x);").is_ok());
+    }
+
+    #[test]
+    fn missing_semicolon_points_past_the_statement_and_recovers() {
+        let source = "let x = 1\nlet y = x\nprint(y);";
+        assert!(matches!(parse(source), Err(ParserError::ExpectedSemicolon(9))));
+
+        let mut parser = Parser::new(tokenize(source).unwrap());
+        parser.set_recover(true);
+        let mut program = Vec::new();
+        while !parser.is_at_end() {
+            program.push(parser.parse_statement().unwrap());
+        }
+        assert_eq!(program.len(), 3);
+        let offsets: Vec<usize> = parser.take_errors().iter().map(ParserError::offset).collect();
+        assert_eq!(offsets, vec![9, 19]);
+    }
+
+    #[test]
+    fn constants_cannot_be_assigned() {
+        match parse("const x = 1;").unwrap().pop() {
+            Some(ASTNode::VarDecNode { constant, .. }) => assert!(constant),
+            other => panic!("expected a declaration, got {:?}", other),
+        }
+        match parse("const limit = 1;\nlimit = 2;") {
+            Err(ParserError::AssignToConst(name, declared, assigned)) => {
+                assert_eq!((name.as_str(), declared, assigned), ("limit", 6, 17));
+            }
+            other => panic!("expected an assignment to a constant, got {:?}", other),
+        }
+        assert!(parse("const x = 1;\nfn f() { let x = 2; x = 3; }").is_ok());
+    }
+
+    // the warnings of a program with every lint on
+    fn warnings(source: &str) -> Vec<ParserWarning> {
+        let mut parser = Parser::new(tokenize(source).unwrap());
+        parser.set_lints(lint::ALL.iter().copied().collect());
+        while !parser.is_at_end() {
+            parser.parse_statement().unwrap();
+        }
+        parser.take_warnings()
+    }
+
+    #[test]
+    fn shadowed_and_repeated_declarations_are_warned_about() {
+        let found = warnings("let x = 1;\nfn f(x) { return x; }\nlet x = 2;\nprint(x);");
+        assert!(matches!(&found[..], [
+            ParserWarning::Shadowed(a, 4, 16),
+            ParserWarning::Redeclared(b, 4, 37),
+        ] if a == "x" && b == "x"), "{:?}", found);
+    }
+
+    #[test]
+    fn lints_report_their_rule() {
+        let found = warnings("fn f(unused) {\n    let x = 1;\n    return x;\n    print(x);\n}\nif (true) { }\nloop (true) { break; }\n");
+        let rules: Vec<Lint> = found.iter().map(ParserWarning::lint).collect();
+        assert_eq!(rules, vec![Lint::UnreachableCode, Lint::UnusedVar, Lint::ConstantCondition]);
+        assert!(matches!(&found[1], ParserWarning::Unused(name, 5) if name == "unused"));
+        assert_eq!(Lint::from_name("unused-var"), Some(Lint::UnusedVar));
+        assert_eq!(Lint::from_name("unused"), None);
+    }
+
+    #[test]
+    fn lints_are_off_unless_enabled() {
+        let mut parser = Parser::new(tokenize("fn f(x) { return 1; }").unwrap());
+        parser.set_lints([Lint::Shadowing].into_iter().collect());
+        parser.parse_statement().unwrap();
+        assert!(parser.take_warnings().is_empty());
+    }
+
+
+    #[test]
+    fn pub_marks_top_level_declarations() {
+        let program = parse("pub fn f() { return 1; }\npub const X = 1;\nlet y = 2;").unwrap();
+        assert!(matches!(&program[0], ASTNode::FuncDef { public: true, .. }));
+        assert!(matches!(&program[1], ASTNode::VarDecNode { public: true, constant: true, .. }));
+        assert!(matches!(&program[2], ASTNode::VarDecNode { public: false, .. }));
+        assert!(matches!(parse("fn f() { pub let x = 1; }"), Err(ParserError::UnexpectedToken(..))));
+        assert!(matches!(parse("pub print(1);"), Err(ParserError::ExpectedToken(..))));
+    }
+
+
+    #[test]
+    fn imports_can_be_aliased_or_selective() {
+        let program = parse("import \"utils/strings\" as s;\nimport \"math\" { sqrt, pow, }\nimport \"io\"").unwrap();
+        assert_eq!(program[0], ASTNode::ImportNode { path: "utils/strings".into(), alias: Some("s".into()), names: vec![] });
+        assert_eq!(program[1], ASTNode::ImportNode { path: "math".into(), alias: None, names: vec!["sqrt".into(), "pow".into()] });
+        assert_eq!(program[2], ASTNode::ImportNode { path: "io".into(), alias: None, names: vec![] });
+        assert!(matches!(parse("import \"math\" { sqrt, sqrt }"), Err(ParserError::UnexpectedToken(..))));
+        assert!(matches!(parse("import \"math\" { }"), Err(ParserError::ExpectedToken(..))));
+    }
+
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn tuples_and_tuple_destructuring() {
+        assert_eq!(value("(1, 2,)"), ASTNode::TupleNode(vec![ASTNode::Integer(1), ASTNode::Integer(2)]));
+        assert_eq!(value("(1,)"), ASTNode::TupleNode(vec![ASTNode::Integer(1)]));
+        assert_eq!(value("(1)"), ASTNode::Integer(1));
+
+        let program = parse("let (a, b) = (1, 2);\n(a, b) = (b, a);").unwrap();
+        assert!(matches!(&program[0], ASTNode::DestructureNode { pattern: Pattern::Tuple(bound), declare: true, .. } if *bound == names(&["a", "b"])));
+        assert!(matches!(&program[1], ASTNode::DestructureNode { declare: false, .. }));
+
+        assert!(matches!(parse("let (a) = 1;"), Err(ParserError::UnexpectedToken(..))));
+        assert!(matches!(parse("let (a, b) = (1, 2, 3);"), Err(ParserError::UnexpectedToken(..))));
+        assert!(matches!(parse("let (a, a) = (1, 2);"), Err(ParserError::UnexpectedToken(..))));
+        assert!(matches!(parse("const (a, b) = (1, 2);\na = 3;"), Err(ParserError::AssignToConst(..))));
+    }
+
+
+    #[test]
+    fn array_and_map_destructuring() {
+        let program = parse("let xs = 1;\nlet [first, second,] = xs;\nconst { name, age } = xs;").unwrap();
+        assert!(matches!(&program[1], ASTNode::DestructureNode { pattern: Pattern::Array(bound), .. } if *bound == names(&["first", "second"])));
+        assert!(matches!(&program[2], ASTNode::DestructureNode { pattern: Pattern::Map(bound), constant: true, .. } if *bound == names(&["name", "age"])));
+        assert_eq!(Pattern::Map(names(&["name", "age"])).to_string(), "{ name, age }");
+        assert!(matches!(parse("let [] = xs;"), Err(ParserError::ExpectedToken(..))));
+        assert!(matches!(parse("let { name: n } = xs;"), Err(ParserError::ExpectedToken(..))));
+    }
+
+
+    fn member(object: ASTNode, field: &str, optional: bool) -> ASTNode {
+        ASTNode::MemberNode { object: Box::new(object), field: field.to_string(), optional }
+    }
+
+    #[test]
+    fn optional_chaining_and_coalescing() {
+        assert_eq!(value("a?.b.c"), member(member(name("a"), "b", true), "c", false));
+        assert!(matches!(value("a?.f(1)"), ASTNode::MethodCall { optional: true, .. }));
+        assert_eq!(value("a ?? b ?? c"), binary(BinaryOp::Coalesce, name("a"), binary(BinaryOp::Coalesce, name("b"), name("c"))));
+        assert_eq!(value("a ?? 0 > 5"), binary(BinaryOp::Gt, binary(BinaryOp::Coalesce, name("a"), ASTNode::Integer(0)), ASTNode::Integer(5)));
+        assert_eq!(value("a ?? 1 + 2"), binary(BinaryOp::Coalesce, name("a"), binary(BinaryOp::Add, ASTNode::Integer(1), ASTNode::Integer(2))));
+    }
+
+
+    #[test]
+    fn in_compares_like_the_other_comparisons() {
+        assert_eq!(value("a + b in c"), binary(BinaryOp::In, binary(BinaryOp::Add, name("a"), name("b")), name("c")));
+        assert_eq!(value("a in b and c"), binary(BinaryOp::And, binary(BinaryOp::In, name("a"), name("b")), name("c")));
+        assert_eq!(BinaryOp::In.symbol(), "in");
+    }
+
+
+    #[test]
+    fn do_while_checks_its_condition_after_the_block() {
+        let program = parse("let i = 0;\ndo { i = i + 1; } while (i < 3);").unwrap();
+        assert!(matches!(&program[1], ASTNode::DoWhileNode { block, condition }
+            if block.len() == 1 && matches!(**condition, ASTNode::BinOpNode { op: BinaryOp::Lt, line: 2, .. })));
+        assert!(matches!(parse("do { } loop (true);"), Err(ParserError::ExpectedToken(..))));
+        assert!(matches!(parse("do { } while (true)"), Err(ParserError::ExpectedSemicolon(_))));
+    }
+
+
+    #[test]
+    fn loop_without_a_condition_runs_until_break() {
+        let program = parse("loop { break; }").unwrap();
+        assert_eq!(program[0], ASTNode::LoopNode { condition: Box::new(ASTNode::BoolNode(true)), block: vec![ASTNode::BreakNode] });
+        assert!(warnings("loop { break; }").is_empty());
+        assert!(matches!(parse("loop break;"), Err(ParserError::ExpectedToken(..))));
+    }
+
+
+    #[test]
+    fn classes_have_fields_methods_and_a_parent() {
+        let program = parse("class Point3 : Point {\n    let z = 1;\n    fn get() { return self.z; }\n}\nlet p = 1;\np.z = 2;").unwrap();
+        match &program[0] {
+            ASTNode::ClassDef { name, parent, fields, methods } => {
+                assert_eq!((name.as_str(), parent.as_deref()), ("Point3", Some("Point")));
+                assert!(matches!(&fields[..], [ASTNode::VarDecNode { name, .. }] if name == "z"));
+                assert!(matches!(&methods[..], [ASTNode::FuncDef { name, .. }] if name == "get"));
+            }
+            other => panic!("expected a class, got {:?}", other),
+        }
+        assert!(matches!(&program[2], ASTNode::FieldAssignNode { field, .. } if field == "z"));
+        assert!(matches!(parse("class A { let x = 1; fn x() { } }"), Err(ParserError::UnexpectedToken(..))));
+        assert!(matches!(parse("class A { print(1); }"), Err(ParserError::ExpectedToken(..))));
+    }
+}
@@ -0,0 +1,79 @@
+// Every source file of a run, addressed by a FileId that stays valid until
+// the manager is dropped. Tokens and parser errors only store byte offsets;
+// lines and columns are worked out here when a diagnostic needs them.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FileId(usize);
+
+struct SourceFile {
+    name: String,
+    text: String,
+    // byte offset at which each line starts, line_starts[0] is always 0
+    line_starts: Vec<usize>,
+}
+
+pub struct SourceManager {
+    files: Vec<SourceFile>,
+}
+
+impl SourceManager {
+
+    pub fn new() -> Self {
+        Self { files: Vec::new() }
+    }
+
+    pub fn add(&mut self, name: &str, text: String) -> FileId {
+        let mut line_starts = vec![0];
+        for (i, byte) in text.bytes().enumerate() {
+            if byte == b'\n' {
+                line_starts.push(i + 1);
+            }
+        }
+
+        self.files.push(SourceFile { name: name.to_string(), text, line_starts });
+        FileId(self.files.len() - 1)
+    }
+
+    pub fn name(&self, id: FileId) -> &str {
+        &self.files[id.0].name
+    }
+
+    pub fn text(&self, id: FileId) -> &str {
+        &self.files[id.0].text
+    }
+
+    // 1-based line and column, columns count characters rather than bytes
+    pub fn line_col(&self, id: FileId, offset: usize) -> (usize, usize) {
+        let file = &self.files[id.0];
+        let offset = offset.min(file.text.len());
+        let line = match file.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+        let col = file.text[file.line_starts[line]..offset].chars().count();
+        (line + 1, col + 1)
+    }
+
+    // file:line:col, the prefix of every diagnostic
+    pub fn location(&self, id: FileId, offset: usize) -> String {
+        let (line, col) = self.line_col(id, offset);
+        format!("{}:{}:{}", self.name(id), line, col)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offsets_become_lines_and_character_columns() {
+        let mut sources = SourceManager::new();
+        let file = sources.add("main.tong", "let a = 1;\nlet é = \"ü\";\n".to_string());
+        assert_eq!(sources.line_col(file, 0), (1, 1));
+        assert_eq!(sources.line_col(file, 10), (1, 11));
+        assert_eq!(sources.line_col(file, 11), (2, 1));
+        // é is two bytes but one column
+        assert_eq!(sources.location(file, 17), "main.tong:2:6");
+        assert_eq!(sources.location(file, 1000), "main.tong:3:1");
+    }
+}
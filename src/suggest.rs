@@ -0,0 +1,47 @@
+// "did you mean" suggestions for misspelled file names, keywords and names
+
+// the candidate closest to wanted, if any is within a third of its length
+// (at least one edit), so short words only match single typos
+pub fn closest<'a>(wanted: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let max_distance = (wanted.chars().count() / 3).max(1);
+    let mut best: Option<(usize, &str)> = None;
+
+    for candidate in candidates {
+        let distance = levenshtein(wanted, candidate);
+        if distance <= max_distance && best.is_none_or(|(d, _)| distance < d) {
+            best = Some((distance, candidate));
+        }
+    }
+
+    best.map(|(_, candidate)| candidate)
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut row = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            row.push((prev[j] + cost).min(prev[j + 1] + 1).min(row[j] + 1));
+        }
+        prev = row;
+    }
+
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn closest_allows_an_edit_per_three_characters() {
+        assert_eq!(closest("fnn", ["fn", "let"]), Some("fn"));
+        assert_eq!(closest("retrun", ["return", "break"]), Some("return"));
+        assert_eq!(closest("ab", ["xy"]), None);
+        assert_eq!(closest("cout", ["count", "coun"]), Some("count"));
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+    }
+}